@@ -0,0 +1,174 @@
+use std::io::{self, Read, Write};
+
+use crate::{checkpoint::Persist, vec3::Color};
+
+/// Settings for spending samples where the image is still noisy, pixels are sampled in
+/// passes (of `min_samples` unless the camera sets the pass size) until their estimate
+/// is confident enough or they reach `max_samples`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32, // samples every pixel gets before it can converge
+    pub max_samples: u32, // samples a pixel stops at even when still noisy
+    pub threshold: f32,   // relative error of the pixel luminance that counts as converged
+}
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: u32, max_samples: u32, threshold: f32) -> Self {
+        let min_samples = min_samples.max(2);
+        Self {
+            min_samples,
+            max_samples: max_samples.max(min_samples),
+            threshold,
+        }
+    }
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self::new(16, 256, 0.05)
+    }
+}
+
+impl Persist for AdaptiveSampling {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.min_samples.write(out)?;
+        self.max_samples.write(out)?;
+        self.threshold.write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            min_samples: u32::read(input)?,
+            max_samples: u32::read(input)?,
+            threshold: f32::read(input)?,
+        })
+    }
+}
+
+/// Running estimate of a pixel, tracking the variance of its luminance with
+/// welford's algorithm
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PixelEstimate {
+    mean: f32, // of the luminance
+    m2: f32,   // sum of squared differences from the mean luminance
+    samples: u32,
+    pub converged: bool,
+}
+
+impl PixelEstimate {
+    pub fn add(&mut self, color: Color) {
+        self.samples += 1;
+
+        let luminance = color.luminance();
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f32;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Half width of the 95% confidence interval relative to the mean luminance,
+    /// dark pixels are measured against a floor so they don't sample forever
+    pub fn error(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+        let n = self.samples as f32;
+        let variance = self.m2 / (n - 1.);
+        1.96 * (variance / n).sqrt() / self.mean.max(0.01)
+    }
+}
+
+impl Persist for PixelEstimate {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.mean.write(out)?;
+        self.m2.write(out)?;
+        self.samples.write(out)?;
+        self.converged.write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            mean: f32::read(input)?,
+            m2: f32::read(input)?,
+            samples: u32::read(input)?,
+            converged: bool::read(input)?,
+        })
+    }
+}
+
+/// Colour ramp for a sample count heatmap, from dark blue at `t` = 0 through red
+/// to yellow at `t` = 1
+pub fn heatmap(t: f32) -> Color {
+    const STOPS: [(f32, f32, f32); 4] = [
+        (0.05, 0.05, 0.3),
+        (0.1, 0.4, 0.9),
+        (0.9, 0.1, 0.1),
+        (1., 1., 0.2),
+    ];
+    let x = t.clamp(0., 1.) * (STOPS.len() - 1) as f32;
+    let i = (x as usize).min(STOPS.len() - 2);
+    let t = x - i as f32;
+    let ((r0, g0, b0), (r1, g1, b1)) = (STOPS[i], STOPS[i + 1]);
+    Color::new(r0 + (r1 - r0) * t, g0 + (g1 - g0) * t, b0 + (b1 - b0) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(value: f32) -> Color {
+        Color::new(value, value, value)
+    }
+
+    // estimate of `samples` samples alternating between black and `value` grey
+    fn alternating(value: f32, samples: u32) -> PixelEstimate {
+        let mut estimate = PixelEstimate::default();
+        for i in 0..samples {
+            estimate.add(grey(if i % 2 == 0 { 0. } else { value }));
+        }
+        estimate
+    }
+
+    #[test]
+    fn error_is_the_relative_confidence_interval() {
+        let mut estimate = PixelEstimate::default();
+        assert_eq!(estimate.error(), f32::INFINITY);
+        estimate.add(grey(0.5));
+        assert_eq!(estimate.error(), f32::INFINITY);
+        estimate.add(grey(0.5));
+        assert_eq!(estimate.error(), 0.);
+
+        // the sample variance of n alternating 0s and 1s is n / 4(n - 1)
+        let n = 100f32;
+        let estimate = alternating(1., 100);
+        let expected = 1.96 * (n / (4. * (n - 1.)) / n).sqrt() / 0.5;
+        assert!((estimate.error() - expected).abs() < 1e-4);
+        assert!((estimate.mean - 0.5).abs() < 1e-6);
+
+        // four times the samples halves the interval
+        let ratio = alternating(1., 100).error() / alternating(1., 400).error();
+        assert!((ratio - 2.).abs() < 0.02, "ratio {ratio}");
+
+        // relative to the mean, so scaling the pixel changes nothing
+        assert!((alternating(10., 100).error() - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn dark_pixels_are_measured_against_a_floor() {
+        let dark = alternating(0.001, 100);
+        let absolute = 1.96 * (0.001f32 * 0.001 * 100. / (4. * 99.) / 100.).sqrt();
+        assert!((dark.error() - absolute / 0.01).abs() < 1e-4);
+        assert!(dark.error() < alternating(1., 100).error());
+    }
+
+    #[test]
+    fn settings_keep_enough_samples() {
+        let adaptive = AdaptiveSampling::new(0, 0, 0.1);
+        assert_eq!((adaptive.min_samples, adaptive.max_samples), (2, 2));
+        let adaptive = AdaptiveSampling::new(32, 8, 0.1);
+        assert_eq!((adaptive.min_samples, adaptive.max_samples), (32, 32));
+    }
+}
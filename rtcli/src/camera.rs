@@ -1,73 +1,377 @@
 use core::f32;
+use std::ops::ControlFlow;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use image::ImageBuffer;
 use log::warn;
 use rayon::prelude::*;
 
 use crate::{
+    adaptive::{AdaptiveSampling, PixelEstimate},
+    checkpoint::{Checkpoint, Checkpointing},
+    film::{Cryptomatte, Features, Film, FilmTile, Filter, ToneMap},
     material::Scatter,
     object::{Hittable, World},
+    prelude::*,
+    progress::{CancelToken, Progress, ProgressObserver},
+    projection::Projection,
     ray::Ray,
+    sampler::Sampler,
+    spectrum::{self, Illuminant, SampledSpectrum, SampledWavelengths},
+    stats::{self, Counter, Stats},
     utils::{
-        self,
         math::{self, deg_to_rad},
-        rng::random_float,
-        Interval,
+        rng, Interval,
     },
     vec3::{Color, Point3, Vec3},
 };
 
+const PASS_SAMPLES: u32 = 4; // samples per pixel in a pass unless set otherwise
+
 pub struct Camera {
     aspect_ratio: f32,                      // ratio of image width / height
     image_width: u32,                       // image width in px
     image_height: u32,                      // image height in px
     camera_pos: Point3,                     // center point of the camera
-    fov: f32,                               // vertical fov of the camera
-    direction: Vec3, // unit vector for the direction the camera is pointing in
-    camera_up: Vec3, // Camera-relative up direction
     camera_basis_frame: (Vec3, Vec3, Vec3), // camera basis frame
     px_top_left: Vec3, // location of the top left pixel in the viewport
     px_dx: Vec3,     // distance between pixels in the x axis in viewport
     px_dy: Vec3,     // distance between pixels in the y axis in viewport
     num_samples: u32, // number of samples taken of each pixel in the frame
-    px_sample_scale: f32, // Color scale factor for a sum of pixel samples
     max_bounce_depth: u32, // maximum number of bounces a ray can perform before expiring
     depth_of_field_angle: f32, // variation angle of rays through each pixel
-    focus_distance: f32, // distance from the camera to the plane of perfect focus
     defocus_disk: (Vec3, Vec3), // defocus disk x and y radius
+    spectral: Option<Illuminant>, // trace spectral paths lit by this illuminant instead of rgb
+    adaptive: Option<AdaptiveSampling>, // sample noisy pixels more than num_samples
+    sampler: Sampler, // source of the random numbers of each sample
+    filter: Filter,  // reconstruction filter splatting samples onto the film
+    exposure: f32,   // exposure of the film in stops
+    tone_map: ToneMap, // tone mapping of the film for display
+    cryptomatte_ranks: Option<usize>, // ids kept per pixel for the mattes, none to skip them
+    tile_size: u32,  // width and height of the tiles rendered at once in px
+    pass_samples: Option<u32>, // samples per pixel in each pass, by default a few
+    checkpointing: Option<Checkpointing>, // where and how often to save the progress
+    resume: Option<Checkpoint>, // progress to carry on from
+    progress: Arc<dyn ProgressObserver>, // told how the render is going
+    cancel: CancelToken, // stops the render when cancelled
+    projection: Projection, // how the film maps to rays
+}
+
+// square of pixels rendered together, with the running estimates of its pixels
+struct Tile {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    pixels: Vec<PixelEstimate>,
+}
+
+impl Tile {
+    fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        Self {
+            x0,
+            y0,
+            x1,
+            y1,
+            pixels: vec![PixelEstimate::default(); ((x1 - x0) * (y1 - y0)) as usize],
+        }
+    }
+
+    fn pixel_mut(&mut self, x: u32, y: u32) -> &mut PixelEstimate {
+        &mut self.pixels[((y - self.y0) * (self.x1 - self.x0) + x - self.x0) as usize]
+    }
+
+    fn converged(&self) -> bool {
+        self.pixels.iter().all(|pixel| pixel.converged)
+    }
+
+    // copies the tile's estimates into / out of those of the whole image, row by row
+    fn store(&self, estimates: &mut [PixelEstimate], width: u32) {
+        let tile_width = (self.x1 - self.x0) as usize;
+        for (row, y) in self.pixels.chunks(tile_width).zip(self.y0..self.y1) {
+            let start = (y * width + self.x0) as usize;
+            estimates[start..start + tile_width].copy_from_slice(row);
+        }
+    }
+
+    fn restore(&mut self, estimates: &[PixelEstimate], width: u32) {
+        let tile_width = (self.x1 - self.x0) as usize;
+        for (row, y) in self.pixels.chunks_mut(tile_width).zip(self.y0..self.y1) {
+            let start = (y * width + self.x0) as usize;
+            row.copy_from_slice(&estimates[start..start + tile_width]);
+        }
+    }
 }
 
 impl Camera {
-    pub fn render(self, world: World) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let mut imgbuf: ImageBuffer<image::Rgb<u8>, Vec<u8>> =
-            ImageBuffer::new(self.image_width, self.image_height);
-
-        let count: AtomicU64 = AtomicU64::new(0);
-        let total = imgbuf.pixels().count() as f32;
-
-        let time_before = Instant::now();
-        imgbuf.par_enumerate_pixels_mut().for_each(|(x, y, px)| {
-            let mut color = Color::new(0., 0., 0.);
-            for _ in 0..self.num_samples {
-                let ray = self.get_ray(x, y);
-                color += Self::ray_color(&ray, &world, self.max_bounce_depth);
+    pub fn render(self, world: World) -> Film {
+        self.render_progressive(world, |_, _| ControlFlow::Continue(()))
+    }
+
+    /// Renders the image in passes over tiles, handing the film to `on_pass` after
+    /// every pass so it can be previewed. Breaking out of `on_pass` or cancelling the
+    /// render stops it early, returning the film as it is
+    pub fn render_progressive(
+        mut self,
+        world: World,
+        mut on_pass: impl FnMut(u32, &Film) -> ControlFlow<()>,
+    ) -> Film {
+        let max_samples = self.max_samples();
+        let pass_samples = self.pass_samples();
+
+        let mut tiles = self.tiles((0, 0, self.image_width, self.image_height));
+        let mut film = self.film(&world);
+
+        // tiles are rendered in parallel batches, merged in order so the film adds up
+        // the same way every run
+        let mut batch_size = rayon::current_num_threads() * 4;
+        let mut pass = 0;
+        let mut start_batch = 0;
+
+        if let Some(checkpoint) = self.resume.take() {
+            film.resume_from(checkpoint.film)
+                .expect("with_resume checks the checkpoint's size");
+            for tile in &mut tiles {
+                tile.restore(&checkpoint.estimates, self.image_width);
+            }
+            (pass, start_batch, batch_size) =
+                (checkpoint.pass, checkpoint.batch, checkpoint.batch_size);
+        }
+
+        let done = AtomicU64::new(
+            tiles
+                .iter()
+                .flat_map(|tile| &tile.pixels)
+                .filter(|pixel| pixel.converged)
+                .count() as u64,
+        );
+        let stats = Mutex::new(Stats::default());
+        let start = Instant::now();
+        let progress = || Progress {
+            done: done.load(Relaxed),
+            total: (self.image_width * self.image_height) as u64,
+            stats: *stats.lock().unwrap(),
+            elapsed: start.elapsed(),
+        };
+
+        let mut last_checkpoint = Instant::now();
+        let batches = tiles.len().div_ceil(batch_size);
+
+        'passes: while tiles.iter().any(|tile| !tile.converged()) {
+            for batch in start_batch..batches {
+                // cancelled between batches, so the progress can still be checkpointed
+                if self.cancel.is_cancelled() {
+                    if let Some(checkpointing) = &self.checkpointing {
+                        self.checkpoint(checkpointing, (pass, batch, batch_size), &film, &tiles);
+                    }
+                    break 'passes;
+                }
+
+                let end = ((batch + 1) * batch_size).min(tiles.len());
+                let rendered: Vec<_> = tiles[batch * batch_size..end]
+                    .par_iter_mut()
+                    .filter(|tile| !tile.converged())
+                    .map(|tile| {
+                        let mut film_tile = film.tile(tile.x0, tile.y0, tile.x1, tile.y1);
+                        let stats_before = Stats::thread();
+                        let converged = self.render_tile(
+                            tile,
+                            &mut film_tile,
+                            pass_samples,
+                            max_samples,
+                            &world,
+                        );
+
+                        done.fetch_add(converged as u64, Relaxed);
+                        stats
+                            .lock()
+                            .unwrap()
+                            .merge(&(Stats::thread() - stats_before));
+                        self.progress.on_progress(&progress());
+                        film_tile
+                    })
+                    .collect();
+
+                for film_tile in &rendered {
+                    film.merge(film_tile);
+                }
+
+                if let Some(checkpointing) = &self.checkpointing {
+                    if last_checkpoint.elapsed() >= checkpointing.interval {
+                        self.checkpoint(
+                            checkpointing,
+                            (pass, batch + 1, batch_size),
+                            &film,
+                            &tiles,
+                        );
+                        last_checkpoint = Instant::now();
+                    }
+                }
+            }
+            start_batch = 0;
+
+            pass += 1;
+            if on_pass(pass, &film).is_break() {
+                // stopped early, keep the progress so the render can be resumed
+                if let Some(checkpointing) = &self.checkpointing {
+                    self.checkpoint(checkpointing, (pass, 0, batch_size), &film, &tiles);
+                }
+                break;
             }
+        }
+        self.progress.on_finish(&progress());
+
+        film
+    }
+
+    /// Renders every sample of the pixels `x0..x1`, `y0..y1` of `region`, splatted
+    /// onto a tile of `film`, along with the events counted rendering it. Lets renders
+    /// be split up between processes
+    pub fn render_region(
+        &self,
+        film: &Film,
+        world: &World,
+        region: (u32, u32, u32, u32),
+    ) -> (FilmTile, Stats) {
+        let (x0, y0, x1, y1) = region;
+        let max_samples = self.max_samples();
+        let pass_samples = self.pass_samples();
+
+        let mut tiles = self.tiles(region);
+        let mut region_tile = film.tile(x0, y0, x1, y1);
+        let mut region_stats = Stats::default();
 
-            *px = image::Rgb((color * self.px_sample_scale).to_gamma().to_rgb());
-            count.fetch_add(1, Relaxed);
-            print!("\rCurrent progress - {:.2}%", (count.load(Relaxed) as f32 / total) * 100.);
-        });
-        let time_after = Instant::now();
-        let time = time_after - time_before;
+        // pass by pass like render_progressive, so the samples add up in the same order
+        // as they do rendering the whole image
+        while tiles.iter().any(|tile| !tile.converged()) {
+            let rendered: Vec<_> = tiles
+                .par_iter_mut()
+                .filter(|tile| !tile.converged())
+                .map(|tile| {
+                    let mut film_tile = film.tile(tile.x0, tile.y0, tile.x1, tile.y1);
+                    let stats_before = Stats::thread();
+                    self.render_tile(tile, &mut film_tile, pass_samples, max_samples, world);
+                    (film_tile, Stats::thread() - stats_before)
+                })
+                .collect();
 
-        println!("Time taken\nNormal\t{}", time.as_millis());
+            for (film_tile, stats) in &rendered {
+                region_tile.merge(film_tile);
+                region_stats.merge(stats);
+            }
+        }
+        (region_tile, region_stats)
+    }
+
+    /// Empty film the camera renders onto
+    pub fn film(&self, world: &World) -> Film {
+        let film = Film::new(self.image_width, self.image_height, self.filter)
+            .with_exposure(self.exposure)
+            .with_tone_map(self.tone_map);
+        match self.cryptomatte_ranks {
+            Some(ranks) => film.with_cryptomatte(Cryptomatte::new(ranks, world)),
+            None => film,
+        }
+    }
 
-        imgbuf
+    // splits the pixels `x0..x1`, `y0..y1` into tiles, row by row
+    fn tiles(&self, (x0, y0, x1, y1): (u32, u32, u32, u32)) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for ty in (y0..y1).step_by(self.tile_size as usize) {
+            for tx in (x0..x1).step_by(self.tile_size as usize) {
+                let tx1 = (tx + self.tile_size).min(x1);
+                let ty1 = (ty + self.tile_size).min(y1);
+                tiles.push(Tile::new(tx, ty, tx1, ty1));
+            }
+        }
+        tiles
+    }
+
+    // saves the progress of a render, failing to only warns as the render can go on
+    fn checkpoint(
+        &self,
+        checkpointing: &Checkpointing,
+        (pass, batch, batch_size): (u32, usize, usize),
+        film: &Film,
+        tiles: &[Tile],
+    ) {
+        let mut estimates =
+            vec![PixelEstimate::default(); (self.image_width * self.image_height) as usize];
+        for tile in tiles {
+            tile.store(&mut estimates, self.image_width);
+        }
+
+        let saved = Checkpoint::save(
+            &checkpointing.path,
+            &checkpointing.job,
+            pass,
+            batch,
+            batch_size,
+            film,
+            &estimates,
+        );
+        if let Err(error) = saved {
+            warn!(
+                "failed to save checkpoint {:?}: {error}",
+                checkpointing.path
+            );
+        }
+    }
+
+    // takes a pass of samples over the unconverged pixels of a tile, returning how many
+    // pixels converged
+    fn render_tile(
+        &self,
+        tile: &mut Tile,
+        film_tile: &mut FilmTile,
+        pass_samples: u32,
+        max_samples: u32,
+        world: &World,
+    ) -> u32 {
+        let mut converged = 0;
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let pixel = tile.pixel_mut(x, y);
+                if pixel.converged {
+                    continue;
+                }
+                for _ in 0..pass_samples.min(max_samples - pixel.samples()) {
+                    let (position, color, features) =
+                        self.sample_pixel(x, y, pixel.samples(), world);
+                    pixel.add(color);
+                    film_tile.add_sample(position, color, &features);
+                }
+
+                pixel.converged = pixel.samples() >= max_samples
+                    || self.adaptive.is_some_and(|adaptive| {
+                        pixel.samples() >= adaptive.min_samples
+                            && pixel.error() < adaptive.threshold
+                    });
+                converged += pixel.converged as u32;
+            }
+        }
+        converged
+    }
+
+    // samples each pixel gets per pass
+    fn pass_samples(&self) -> u32 {
+        self.pass_samples.unwrap_or(match self.adaptive {
+            Some(adaptive) => adaptive.min_samples,
+            None => PASS_SAMPLES,
+        })
+    }
+
+    /// Samples a pixel stops at, the most any pixel of the image gets
+    pub fn max_samples(&self) -> u32 {
+        match self.adaptive {
+            Some(adaptive) => adaptive.max_samples,
+            None => self.num_samples,
+        }
     }
 
+    #[allow(clippy::too_many_arguments)] // every scene spells its camera out in full
     pub fn from(
         aspect_ratio: f32,
         image_width: u32,
@@ -100,9 +404,6 @@ impl Camera {
         let v = w.cross(&u);
         let camera_basis_frame = (w, u, v);
 
-        // sampling
-        let px_sample_scale = 1. / num_samples as f32;
-
         // direction to render pixels in
         let viewport_x = viewport_width * u;
         let viewport_y = viewport_height * -v; // invert viewport height to start top and go to bottom
@@ -127,23 +428,201 @@ impl Camera {
             px_dx,
             px_dy,
             num_samples,
-            px_sample_scale,
             max_bounce_depth,
-            fov,
-            direction,
-            camera_up,
             camera_basis_frame,
             depth_of_field_angle,
-            focus_distance,
             defocus_disk,
+            spectral: None,
+            adaptive: None,
+            sampler: Sampler::default(),
+            filter: Filter::default(),
+            exposure: 0.,
+            tone_map: ToneMap::default(),
+            cryptomatte_ranks: None,
+            tile_size: 32,
+            pass_samples: None,
+            checkpointing: None,
+            resume: None,
+            progress: Arc::new(()),
+            cancel: CancelToken::default(),
+            projection: Projection::default(),
         }
     }
 
-    fn get_ray(&self, x: u32, y: u32) -> Ray {
+    /// Renders with spectral paths instead of rgb, lit by `illuminant`
+    pub fn with_spectral(mut self, illuminant: Illuminant) -> Self {
+        self.spectral = Some(illuminant);
+        self
+    }
+
+    /// Samples pixels in passes until their noise drops below the threshold instead of
+    /// a fixed `num_samples` each
+    pub fn with_adaptive_sampling(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    /// Draws the random numbers of each sample from `sampler`
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Reconstructs the image from the samples with `filter` instead of averaging the
+    /// samples of each pixel
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Brightens the image by `exposure` stops, negative values darken it
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// Renders the image in square tiles of `tile_size` px
+    #[cfg(test)] // renders always use the default, tests shrink it to get several tiles
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Takes `pass_samples` samples per pixel in each progressive pass instead of a few
+    /// (or adaptive sampling's `min_samples`), fewer show a preview sooner
+    pub fn with_pass_samples(mut self, pass_samples: u32) -> Self {
+        self.pass_samples = Some(pass_samples.max(1));
+        self
+    }
+
+    /// Saves the progress of the render every `checkpointing.interval`, to be picked up
+    /// again with `with_resume` if the render dies
+    pub fn with_checkpoints(mut self, checkpointing: Checkpointing) -> Self {
+        self.checkpointing = Some(checkpointing);
+        self
+    }
+
+    /// Carries on from the progress saved in `checkpoint`, which must have been made
+    /// by the same camera and scene for the result to match an uninterrupted render
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Result<Self> {
+        let (width, height) = (checkpoint.film.width(), checkpoint.film.height());
+        if (width, height) != (self.image_width, self.image_height)
+            || checkpoint.estimates.len() != (width * height) as usize
+            || checkpoint.batch_size == 0
+        {
+            return Err(Error::Generic(format!(
+                "checkpoint of a {width}x{height} render doesn't fit a {}x{} camera",
+                self.image_width, self.image_height
+            )));
+        }
+        self.resume = Some(checkpoint);
+        Ok(self)
+    }
+
+    /// Keeps object and material mattes of the `ranks` ids covering each pixel most,
+    /// for the cryptomatte layers of the film's layered exr
+    pub fn with_cryptomatte(mut self, ranks: usize) -> Self {
+        self.cryptomatte_ranks = Some(ranks);
+        self
+    }
+
+    /// Maps the film to rays with `projection` instead of the perspective viewport. The
+    /// panoramic projections resize the image to their shape, keeping its width
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        if let Some(aspect_ratio) = projection.aspect_ratio() {
+            self.aspect_ratio = aspect_ratio;
+            self.image_height = ((self.image_width as f32 / aspect_ratio) as u32).max(1);
+        }
+        self
+    }
+
+    /// Tells `observer` how the render is going, by default nothing is told
+    pub fn with_progress(mut self, observer: impl ProgressObserver + 'static) -> Self {
+        self.progress = Arc::new(observer);
+        self
+    }
+
+    /// Stops the render once `cancel` is cancelled, keep a clone of it to cancel with
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    // traces the `index`th path through the pixel, returning where on the film it
+    // started, its colour and what it hit first
+    fn sample_pixel(
+        &self,
+        x: u32,
+        y: u32,
+        index: u32,
+        world: &World,
+    ) -> ((f32, f32), Color, Features) {
+        rng::start_sample(self.sampler, (x, y), index);
+        let (ray, position) = self.get_ray(x, y);
+        let mut features = Features::default();
+        let color = match (ray, &self.spectral) {
+            // off the image, like outside the circle of a fisheye
+            (None, _) => Color::new(0., 0., 0.),
+            (Some(ray), Some(illuminant)) => {
+                let mut wavelengths = SampledWavelengths::sample();
+                let ray = ray.with_wavelength(Some(wavelengths.hero()));
+                let radiance = Self::ray_radiance_spectral(
+                    &ray,
+                    world,
+                    self.max_bounce_depth,
+                    illuminant,
+                    &mut wavelengths,
+                    &mut features,
+                );
+                wavelengths.to_rgb(&radiance)
+            }
+            (Some(ray), None) => Self::ray_color(&ray, world, self.max_bounce_depth, &mut features),
+        };
+        rng::end_sample();
+        features.indirect = color - features.direct;
+
+        (position, color, features)
+    }
+
+    // ray through a random point of a pixel and where on the film that point is, no
+    // ray when the projection leaves the point out of the image
+    fn get_ray(&self, x: u32, y: u32) -> (Option<Ray>, (f32, f32)) {
         let x = x as f32;
         let y = y as f32;
 
         let offset = Self::sample_square();
+        // px_top_left is the centre of the first pixel. Rounding can carry a point by the
+        // far edge of a pixel into the next one, so it's kept inside to be splatted with
+        // the pixel it was taken for, whichever tile that pixel is rendered in
+        let position = (
+            (x + 0.5 + offset.x()).min((x + 1.).next_down()),
+            (y + 0.5 + offset.y()).min((y + 1.).next_down()),
+        );
+
+        if self.projection != Projection::Perspective {
+            let film = (
+                position.0 / self.image_width as f32,
+                position.1 / self.image_height as f32,
+            );
+            let aspect_ratio = self.image_width as f32 / self.image_height as f32;
+            let ray = self
+                .projection
+                .ray(film, aspect_ratio)
+                .map(|(origin, direction)| {
+                    Ray::new(
+                        self.camera_pos + self.to_world(origin),
+                        self.to_world(direction),
+                    )
+                });
+            return (ray, position);
+        }
+
         let px_sample =
             self.px_top_left + ((x + offset.x()) * self.px_dx) + ((y + offset.y()) * self.px_dy);
 
@@ -154,25 +633,122 @@ impl Camera {
         };
 
         let direction = px_sample - origin;
-        Ray::new(origin, direction)
+        (Some(Ray::new(origin, direction)), position)
     }
 
-    fn ray_color(ray: &Ray, world: &World, num_bounces: u32) -> Color {
-        if num_bounces == 0 {
-            // hit recursion limit
-            return Color::new(0., 0., 0.);
-        }
-        if let Some(record) = world.hit(ray, Interval::from(0.001, f32::INFINITY)) {
+    // from camera space, x right, y up and z forward, to world space
+    fn to_world(&self, vector: Vec3) -> Vec3 {
+        let (w, u, v) = self.camera_basis_frame;
+        // w points back from the view direction
+        vector.x() * u + vector.y() * v - vector.z() * w
+    }
+
+    fn ray_color(ray: &Ray, world: &World, max_bounces: u32, features: &mut Features) -> Color {
+        let mut ray = *ray;
+        let mut throughput = Color::new(1., 1., 1.);
+
+        // past the bounce limit the path carries no more light
+        for bounce in 0..max_bounces {
+            rng::next_bounce();
+            stats::count(if bounce == 0 {
+                Counter::CameraRays
+            } else {
+                Counter::BounceRays
+            });
             // from 0.001 to fix shadow acne, where rays bounce many times off same point
+            let Some(record) = world.hit(&ray, Interval::from(0.001, f32::INFINITY)) else {
+                let background = Self::background(&ray);
+                if bounce == 0 {
+                    features.albedo = background;
+                }
+                let radiance = throughput * background;
+                if bounce <= 1 {
+                    features.direct = radiance;
+                }
+                return radiance;
+            };
+
+            // the first dispersive surface on a path picks the wavelength the rest of the
+            // path carries, weighting it by that wavelength's colour
+            if ray.wavelength().is_none() && record.material.is_dispersive() {
+                let wavelength = spectrum::sample_wavelength();
+                throughput = throughput * spectrum::wavelength_to_rgb(wavelength);
+                ray = ray.with_wavelength(Some(wavelength));
+            }
 
-            if let Some((scattered_ray, attenuation)) = record.material.scatter(ray, &record) {
-                return attenuation * Self::ray_color(&scattered_ray, world, num_bounces - 1);
+            stats::count(Counter::BsdfEvaluations);
+            let Some((scattered_ray, attenuation)) = record.material.scatter(&ray, &record)
+            else {
+                break;
             };
+            if bounce == 0 {
+                *features = Features::from_hit(&ray, &record, attenuation);
+            }
 
-            return Color::new(0., 0., 0.);
+            throughput = throughput * attenuation;
+            ray = scattered_ray;
         }
 
-        // background color
+        Color::new(0., 0., 0.)
+    }
+
+    fn ray_radiance_spectral(
+        ray: &Ray,
+        world: &World,
+        max_bounces: u32,
+        illuminant: &Illuminant,
+        wavelengths: &mut SampledWavelengths,
+        features: &mut Features,
+    ) -> SampledSpectrum {
+        let mut ray = *ray;
+        let mut throughput = SampledSpectrum::splat(1.);
+
+        for bounce in 0..max_bounces {
+            rng::next_bounce();
+            stats::count(if bounce == 0 {
+                Counter::CameraRays
+            } else {
+                Counter::BounceRays
+            });
+            let Some(record) = world.hit(&ray, Interval::from(0.001, f32::INFINITY)) else {
+                let background = Self::background(&ray);
+                if bounce == 0 {
+                    features.albedo = background;
+                }
+                let radiance = throughput
+                    * SampledSpectrum::from_rgb(&background, wavelengths)
+                    * illuminant.sample(wavelengths);
+                if bounce <= 1 {
+                    features.direct = wavelengths.to_rgb(&radiance);
+                }
+                return radiance;
+            };
+
+            // the ray already carries the hero wavelength, so a dispersive surface only
+            // leaves the others without a valid path
+            if record.material.is_dispersive() {
+                wavelengths.terminate_secondary();
+            }
+
+            stats::count(Counter::BsdfEvaluations);
+            let Some((scattered_ray, attenuation)) =
+                record.material.scatter_spectral(&ray, &record, wavelengths)
+            else {
+                break;
+            };
+            if bounce == 0 {
+                let albedo = wavelengths.to_rgb(&attenuation);
+                *features = Features::from_hit(&ray, &record, albedo);
+            }
+
+            throughput = throughput * attenuation;
+            ray = scattered_ray;
+        }
+
+        SampledSpectrum::splat(0.)
+    }
+
+    fn background(ray: &Ray) -> Color {
         let unit_direction = ray.direction().unit();
         let scale = 0.5 * (unit_direction.y() + 1.); // blend in the y axis, midpoint halfway down
 
@@ -181,7 +757,8 @@ impl Camera {
     }
 
     fn sample_square() -> Vec3 {
-        Vec3::new(utils::rng::random_float() - 0.5, random_float() - 0.5, 0.)
+        let (x, y) = rng::random_2d();
+        Vec3::new(x - 0.5, y - 0.5, 0.)
     }
 
     fn depth_of_field_disk_sample(&self) -> Point3 {
@@ -0,0 +1,451 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    adaptive::PixelEstimate,
+    distributed::Job,
+    film::{Features, Film},
+    prelude::*,
+    vec3::{Color, Vec3},
+};
+
+const MAGIC: &[u8; 8] = b"rtckpt03";
+
+/// Value written to a checkpoint and read back bit for bit, so a resumed render
+/// carries on exactly where it stopped
+pub trait Persist: Sized {
+    fn write(&self, out: &mut impl Write) -> io::Result<()>;
+    fn read(input: &mut impl Read) -> io::Result<Self>;
+}
+
+impl Persist for u32 {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.to_le_bytes())
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        let mut bytes = [0; 4];
+        input.read_exact(&mut bytes)?;
+        Ok(Self::from_le_bytes(bytes))
+    }
+}
+
+impl Persist for u64 {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.to_le_bytes())
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        let mut bytes = [0; 8];
+        input.read_exact(&mut bytes)?;
+        Ok(Self::from_le_bytes(bytes))
+    }
+}
+
+impl Persist for f32 {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.to_bits().write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        u32::read(input).map(f32::from_bits)
+    }
+}
+
+impl Persist for bool {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&[*self as u8])
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        Ok(byte[0] != 0)
+    }
+}
+
+// length prefixed utf-8, no longer than a name or path would be so a corrupt length
+// can't allocate the memory away
+impl Persist for String {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        (self.len() as u64).write(out)?;
+        out.write_all(self.as_bytes())
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        const MAX_LEN: u64 = 1 << 16;
+        let len = u64::read(input)?;
+        if len > MAX_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("string of {len} bytes is too long"),
+            ));
+        }
+        let mut bytes = vec![0; len as usize];
+        input.read_exact(&mut bytes)?;
+        String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+impl Persist for Vec3 {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.x().write(out)?;
+        self.y().write(out)?;
+        self.z().write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(Vec3::new(
+            f32::read(input)?,
+            f32::read(input)?,
+            f32::read(input)?,
+        ))
+    }
+}
+
+impl Persist for Color {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.r().write(out)?;
+        self.g().write(out)?;
+        self.b().write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(Color::new(
+            f32::read(input)?,
+            f32::read(input)?,
+            f32::read(input)?,
+        ))
+    }
+}
+
+impl<A: Persist, B: Persist> Persist for (A, B) {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.0.write(out)?;
+        self.1.write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok((A::read(input)?, B::read(input)?))
+    }
+}
+
+// length prefixed, read back as a vec
+fn write_slice<T: Persist>(items: &[T], out: &mut impl Write) -> io::Result<()> {
+    (items.len() as u64).write(out)?;
+    items.iter().try_for_each(|item| item.write(out))
+}
+
+// no more items than pixels of a 16k by 16k film, and room is made for them a chunk at
+// a time as they're read so a corrupt length runs out of input before it runs out of
+// memory
+impl<T: Persist> Persist for Vec<T> {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        write_slice(self, out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        const MAX_LEN: u64 = 1 << 28;
+        const CHUNK: u64 = 1 << 12;
+        let len = u64::read(input)?;
+        if len > MAX_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("list of {len} items is too long"),
+            ));
+        }
+        let mut items = Vec::new();
+        for read in 0..len {
+            if read % CHUNK == 0 {
+                items.reserve(CHUNK.min(len - read) as usize);
+            }
+            items.push(T::read(input)?);
+        }
+        Ok(items)
+    }
+}
+
+impl Persist for Features {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.albedo.write(out)?;
+        self.normal.write(out)?;
+        self.depth.write(out)?;
+        self.position.write(out)?;
+        self.u.write(out)?;
+        self.v.write(out)?;
+        self.direct.write(out)?;
+        self.indirect.write(out)?;
+        self.object_id.write(out)?;
+        self.material_id.write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(Features {
+            albedo: Color::read(input)?,
+            normal: Vec3::read(input)?,
+            depth: f32::read(input)?,
+            position: Vec3::read(input)?,
+            u: f32::read(input)?,
+            v: f32::read(input)?,
+            direct: Color::read(input)?,
+            indirect: Color::read(input)?,
+            object_id: u32::read(input)?,
+            material_id: u32::read(input)?,
+        })
+    }
+}
+
+/// Where and how often a render saves its progress
+#[derive(Debug, Clone)]
+pub struct Checkpointing {
+    pub path: PathBuf,
+    pub interval: Duration, // least time between two checkpoints
+    pub job: Job,           // what's rendered, to check it's the same on resume
+}
+
+/// Progress of an interrupted render: the samples splatted onto the film, the running
+/// estimate of every pixel and how far through its passes the render got
+pub struct Checkpoint {
+    pub job: Job,
+    pub pass: u32,         // passes completed
+    pub batch: usize,      // batches of tiles completed in the current pass
+    pub batch_size: usize, // tiles per batch, resuming must batch them the same way
+    pub film: Film,
+    pub estimates: Vec<PixelEstimate>, // row by row
+}
+
+impl Checkpoint {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut input = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::Generic(String::from("not a render checkpoint")));
+        }
+
+        Ok(Self {
+            job: Job::read(&mut input)?,
+            pass: u32::read(&mut input)?,
+            batch: u64::read(&mut input)? as usize,
+            batch_size: u64::read(&mut input)? as usize,
+            film: Film::read_samples(&mut input)?,
+            estimates: Vec::read(&mut input)?,
+        })
+    }
+
+    /// Writes the progress of a render to `path` through a temporary file, so being
+    /// killed while saving leaves the previous checkpoint intact
+    pub fn save(
+        path: impl AsRef<Path>,
+        job: &Job,
+        pass: u32,
+        batch: usize,
+        batch_size: usize,
+        film: &Film,
+        estimates: &[PixelEstimate],
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+
+        let mut out = BufWriter::new(File::create(&temporary)?);
+        out.write_all(MAGIC)?;
+        job.write(&mut out)?;
+        pass.write(&mut out)?;
+        (batch as u64).write(&mut out)?;
+        (batch_size as u64).write(&mut out)?;
+        film.write_samples(&mut out)?;
+        write_slice(estimates, &mut out)?;
+        out.into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_all()?;
+
+        fs::rename(temporary, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        ops::ControlFlow,
+        process,
+        sync::atomic::{AtomicUsize, Ordering::Relaxed},
+    };
+
+    use super::*;
+    use crate::{
+        camera::Camera,
+        material::{Dielectric, Diffuse, Material},
+        object::{Object, Sphere, World},
+        progress::{CancelToken, Progress, ProgressObserver},
+        vec3::Point3,
+    };
+
+    // a few spheres rendered in three passes over several batches of tiles
+    fn scene() -> (Camera, World) {
+        let mut world = World::new();
+        let ground = Material::Diffuse(Diffuse::new(&Color::new(0.5, 0.5, 0.5)));
+        let glass = Material::Dielectric(Dielectric::new(1.5));
+        world.push(Object::Sphere(Sphere::new(
+            Point3::new(0., -100.5, -1.),
+            100.,
+            ground,
+        )));
+        world.push(Object::Sphere(Sphere::new(
+            Point3::new(0., 0., -1.),
+            0.5,
+            glass,
+        )));
+
+        let camera = Camera::from(
+            16. / 9.,
+            48,
+            6,
+            8,
+            60.,
+            1.,
+            0.,
+            Vec3::new(0., 0., 1.),
+            Vec3::new(0., 1., 0.),
+            Point3::new(0., 0., 0.),
+        )
+        .with_tile_size(8)
+        .with_pass_samples(2);
+        (camera, world)
+    }
+
+    fn checkpointing(name: &str) -> Checkpointing {
+        Checkpointing {
+            path: env::temp_dir().join(format!("rt-{name}-{}.ckpt", process::id())),
+            interval: Duration::MAX,
+            job: Job {
+                scene: String::from("test"),
+                seed: 0,
+                cryptomatte: None,
+                projection: None,
+                pass_samples: None,
+                adaptive: None,
+                spectral: None,
+                sampler: None,
+                filter: None,
+            },
+        }
+    }
+
+    fn samples(film: &Film) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        film.write_samples(&mut bytes).unwrap();
+        bytes
+    }
+
+    // carries on from the checkpoint saved by the interrupted render
+    fn resume(checkpointing: Checkpointing) -> Film {
+        let checkpoint = Checkpoint::load(&checkpointing.path).unwrap();
+        let _ = fs::remove_file(&checkpointing.path);
+        let (camera, world) = scene();
+        camera
+            .with_checkpoints(checkpointing)
+            .with_resume(checkpoint)
+            .unwrap()
+            .render(world)
+    }
+
+    // cancels the render once it's rendered a number of tiles
+    struct CancelAfter {
+        tiles: AtomicUsize,
+        cancel: CancelToken,
+    }
+
+    impl ProgressObserver for CancelAfter {
+        fn on_progress(&self, _progress: &Progress) {
+            if self.tiles.fetch_sub(1, Relaxed) == 1 {
+                self.cancel.cancel();
+            }
+        }
+    }
+
+    #[test]
+    fn resuming_a_stopped_render_matches_an_uninterrupted_one() {
+        let (camera, world) = scene();
+        let uninterrupted = samples(&camera.render(world));
+
+        let checkpointing = checkpointing("stopped");
+        let (camera, world) = scene();
+        camera
+            .with_checkpoints(checkpointing.clone())
+            .render_progressive(world, |pass, _| match pass {
+                1 => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            });
+
+        assert!(samples(&resume(checkpointing)) == uninterrupted);
+    }
+
+    #[test]
+    fn resuming_a_cancelled_render_matches_an_uninterrupted_one() {
+        let (camera, world) = scene();
+        let uninterrupted = samples(&camera.render(world));
+
+        // part way through the second pass
+        let checkpointing = checkpointing("cancelled");
+        let cancel = CancelToken::new();
+        let (camera, world) = scene();
+        camera
+            .with_checkpoints(checkpointing.clone())
+            .with_cancel(cancel.clone())
+            .with_progress(CancelAfter {
+                tiles: AtomicUsize::new(30),
+                cancel,
+            })
+            .render(world);
+
+        assert!(samples(&resume(checkpointing)) == uninterrupted);
+    }
+
+    #[test]
+    fn corrupt_checkpoints_are_errors() {
+        let checkpointing = checkpointing("corrupt");
+        let (camera, world) = scene();
+        camera
+            .with_checkpoints(checkpointing.clone())
+            .render_progressive(world, |_, _| ControlFlow::Break(()));
+        let bytes = fs::read(&checkpointing.path).unwrap();
+        let load = |bytes: &[u8]| {
+            fs::write(&checkpointing.path, bytes).unwrap();
+            Checkpoint::load(&checkpointing.path)
+        };
+        assert!(load(&bytes).is_ok());
+
+        // cut short anywhere
+        for len in (0..bytes.len()).step_by(bytes.len() / 97 + 1) {
+            assert!(load(&bytes[..len]).is_err(), "truncated to {len} bytes");
+        }
+
+        // garbage anywhere may read back as other samples but mustn't panic
+        for at in (0..bytes.len() - 8).step_by(bytes.len() / 53 + 1) {
+            let mut corrupt = bytes.clone();
+            corrupt[at..at + 8].fill(0xff);
+            let _ = load(&corrupt);
+        }
+        let _ = fs::remove_file(&checkpointing.path);
+
+        // sizes that would overflow or allocate without end
+        let mut film = Vec::new();
+        u32::MAX.write(&mut film).unwrap();
+        u32::MAX.write(&mut film).unwrap();
+        u64::MAX.write(&mut film).unwrap();
+        assert!(Film::read_samples(&mut film.as_slice()).is_err());
+
+        let mut list = Vec::new();
+        (1u64 << 27).write(&mut list).unwrap();
+        1f32.write(&mut list).unwrap();
+        assert!(Vec::<f32>::read(&mut list.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    adaptive::AdaptiveSampling,
+    film::{cryptomatte, Aov, Denoiser, Filter, ToneMap},
+    prelude::*,
+    projection::Projection,
+    sampler::Sampler,
+    scenes,
+    spectrum::Illuminant,
+    stats::StatsFormat,
+};
+
+pub const USAGE: &str = "\
+Usage: ray-tracing [OPTIONS]
+
+Options:
+  --scene <NAME>      scene to render [default: large_scene]
+  --output <PATH>     image to write, the format follows the extension
+                      (exr, hdr, pfm or png) [default: image.png]
+  --png-depth <BITS>  bits per channel of png outputs, 8 or 16 [default: 8]
+  --exposure <EV>     exposure adjustment in stops
+  --tonemap <OP>      clamp, reinhard, reinhard-extended[=WHITE], aces or agx
+  --projection <NAME> perspective, orthographic[=HEIGHT], fisheye[=FOV],
+                      fisheye-equisolid[=FOV], equirectangular, cubemap,
+                      stereo-top-bottom[=IPD] or stereo-side-by-side[=IPD], panoramas
+                      keep the width of the image and take their own shape
+  --spectral <LIGHT>  trace spectral paths instead of rgb, lit by equal, d65,
+                      tungsten or blackbody[=KELVIN]
+  --sampler <NAME>    random numbers of the samples: independent, stratified,
+                      halton, sobol or blue-noise
+  --filter <NAME>     reconstruction filter: box, tent, gaussian, mitchell or
+                      lanczos, with an optional =RADIUS in pixels
+  --denoise           denoise the image, guided by the albedo, normals and depth
+  --denoise-iterations <N>
+                      passes of the denoiser, more blurs further [default: 5]
+  --aov <NAMES>       comma separated passes to write next to the image, layers of
+                      an exr output or <stem>.<pass>.<ext> files otherwise: depth,
+                      normal, albedo, position, uv, object_id, material_id, direct,
+                      indirect, samples or all
+  --cryptomatte       write object and material mattes as cryptomatte layers of an
+                      exr output, or of <stem>.cryptomatte.exr otherwise
+  --cryptomatte-ranks <N>
+                      ids kept per pixel in the mattes, at most 16 [default: 6]
+  --pass-samples <N>  samples per pixel in each pass over the image [default: 4, or
+                      the min samples when sampling adaptively]
+  --adaptive          keep sampling the noisy pixels once the rest have converged
+  --min-samples <N>   samples of every pixel before it can converge, implies
+                      --adaptive [default: 16]
+  --max-samples <N>   samples a pixel stops at, implies --adaptive [default: 256]
+  --adaptive-threshold <ERROR>
+                      relative error of a pixel that counts as converged, implies
+                      --adaptive [default: 0.05]
+  --heatmap           write the samples taken per pixel to <stem>.heatmap.png
+  --preview <PATH>    save the image to PATH after passes as it's rendered
+  --preview-interval <SECS>
+                      least time between previews [default: 5]
+  --checkpoint <PATH> save the progress of the render to PATH as it goes
+  --checkpoint-interval <SECS>
+                      least time between checkpoints [default: 60]
+  --resume            carry on from the checkpoint, given the same options
+  --seed <N>          seed of the random numbers scenes are built from
+  --coordinator <ADDR>
+                      listen on ADDR and render with the workers that connect
+  --worker <ADDR>     render regions for the coordinator at ADDR, every other
+                      option comes from the coordinator
+  --quiet             don't show the progress of the render
+  --stats <FORMAT>    print counts of rays, intersection tests and other events once
+                      the render is done, as a table or json
+  --help              print this message";
+
+/// Options from the command line, the scene's own settings are only overridden by
+/// the ones that were given
+#[derive(Debug)]
+pub struct Args {
+    pub scene: String,
+    pub output: PathBuf,
+    pub png_depth: u8, // bits per channel of png images
+    pub exposure: Option<f32>,
+    pub tone_map: Option<ToneMap>,
+    pub projection: Option<Projection>,
+    pub spectral: Option<Illuminant>,
+    pub sampler: Option<Sampler>,
+    pub filter: Option<Filter>,
+    pub denoise: Option<Denoiser>,
+    pub aovs: Vec<Aov>,
+    pub cryptomatte: Option<usize>,
+    pub pass_samples: Option<u32>,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub heatmap: bool, // write a heatmap of the samples per pixel
+    pub preview: Option<PathBuf>,
+    pub preview_interval: Duration,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    pub resume: bool,
+    pub seed: Option<u64>,
+    pub coordinator: Option<String>, // address to listen for workers on
+    pub worker: Option<String>,      // address of the coordinator to render for
+    pub quiet: bool,                 // no progress bar
+    pub stats: Option<StatsFormat>,  // how to report the render's statistics
+}
+
+impl Args {
+    /// Parses the arguments after the program name, none when help was asked for
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>> {
+        let mut parsed = Args {
+            scene: String::from("large_scene"),
+            output: PathBuf::from("image.png"),
+            png_depth: 8,
+            exposure: None,
+            tone_map: None,
+            projection: None,
+            spectral: None,
+            sampler: None,
+            filter: None,
+            denoise: None,
+            aovs: Vec::new(),
+            cryptomatte: None,
+            pass_samples: None,
+            adaptive: None,
+            heatmap: false,
+            preview: None,
+            preview_interval: Duration::from_secs(5),
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+            resume: false,
+            seed: None,
+            coordinator: None,
+            worker: None,
+            quiet: false,
+            stats: None,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| Error::Generic(format!("missing value for {arg}\n\n{USAGE}")))
+            };
+
+            match arg.as_str() {
+                "--scene" => parsed.scene = value()?,
+                "--output" => parsed.output = PathBuf::from(value()?),
+                "--png-depth" => {
+                    let bits = value()?;
+                    parsed.png_depth = match bits.as_str() {
+                        "8" => 8,
+                        "16" => 16,
+                        _ => {
+                            return Err(Error::Generic(format!(
+                                "invalid png depth {bits}, expected 8 or 16"
+                            )))
+                        }
+                    };
+                }
+                "--exposure" => {
+                    let exposure = value()?;
+                    parsed.exposure = Some(
+                        exposure
+                            .parse()
+                            .map_err(|_| Error::Generic(format!("invalid exposure {exposure}")))?,
+                    );
+                }
+                "--tonemap" => parsed.tone_map = Some(value()?.parse()?),
+                "--projection" => parsed.projection = Some(value()?.parse()?),
+                "--spectral" => parsed.spectral = Some(value()?.parse()?),
+                "--sampler" => parsed.sampler = Some(value()?.parse()?),
+                "--filter" => parsed.filter = Some(value()?.parse()?),
+                "--denoise" => parsed.denoise = Some(parsed.denoise.unwrap_or_default()),
+                "--denoise-iterations" => {
+                    let iterations = value()?;
+                    parsed.denoise = Some(Denoiser {
+                        iterations: iterations.parse().map_err(|_| {
+                            Error::Generic(format!("invalid denoise iterations {iterations}"))
+                        })?,
+                        ..parsed.denoise.unwrap_or_default()
+                    });
+                }
+                "--aov" => {
+                    let names = value()?;
+                    parsed.aovs = if names == "all" {
+                        Aov::ALL.to_vec()
+                    } else {
+                        names.split(',').map(str::parse).collect::<Result<_>>()?
+                    };
+                }
+                "--cryptomatte" => parsed.cryptomatte = Some(parsed.cryptomatte.unwrap_or(6)),
+                "--cryptomatte-ranks" => {
+                    let ranks = value()?;
+                    parsed.cryptomatte = Some(
+                        ranks
+                            .parse()
+                            .ok()
+                            .filter(|ranks| *ranks <= cryptomatte::MAX_IDS)
+                            .ok_or_else(|| {
+                                Error::Generic(format!("invalid cryptomatte ranks {ranks}"))
+                            })?,
+                    );
+                }
+                "--pass-samples" => {
+                    let samples = value()?;
+                    parsed.pass_samples = Some(
+                        samples
+                            .parse()
+                            .ok()
+                            .filter(|samples| *samples > 0)
+                            .ok_or_else(|| {
+                                Error::Generic(format!("invalid pass samples {samples}"))
+                            })?,
+                    );
+                }
+                "--adaptive" => parsed.adaptive = Some(parsed.adaptive.unwrap_or_default()),
+                "--min-samples" | "--max-samples" => {
+                    let samples = value()?;
+                    let samples = samples
+                        .parse()
+                        .ok()
+                        .filter(|samples| *samples > 0)
+                        .ok_or_else(|| Error::Generic(format!("invalid {arg} {samples}")))?;
+                    let mut adaptive = parsed.adaptive.unwrap_or_default();
+                    match arg.as_str() {
+                        "--min-samples" => adaptive.min_samples = samples,
+                        _ => adaptive.max_samples = samples,
+                    }
+                    parsed.adaptive = Some(adaptive);
+                }
+                "--adaptive-threshold" => {
+                    let threshold = value()?;
+                    parsed.adaptive = Some(AdaptiveSampling {
+                        threshold: threshold
+                            .parse()
+                            .ok()
+                            .filter(|threshold| *threshold > 0.)
+                            .ok_or_else(|| {
+                                Error::Generic(format!("invalid adaptive threshold {threshold}"))
+                            })?,
+                        ..parsed.adaptive.unwrap_or_default()
+                    });
+                }
+                "--heatmap" => parsed.heatmap = true,
+                "--preview" => parsed.preview = Some(PathBuf::from(value()?)),
+                "--preview-interval" => {
+                    let interval = value()?;
+                    parsed.preview_interval = interval
+                        .parse()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f32(secs).ok())
+                        .ok_or_else(|| {
+                            Error::Generic(format!("invalid preview interval {interval}"))
+                        })?;
+                }
+                "--checkpoint" => parsed.checkpoint = Some(PathBuf::from(value()?)),
+                "--checkpoint-interval" => {
+                    let interval = value()?;
+                    parsed.checkpoint_interval = interval
+                        .parse()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f32(secs).ok())
+                        .ok_or_else(|| {
+                            Error::Generic(format!("invalid checkpoint interval {interval}"))
+                        })?;
+                }
+                "--resume" => parsed.resume = true,
+                "--seed" => {
+                    let seed = value()?;
+                    parsed.seed = Some(
+                        seed.parse()
+                            .map_err(|_| Error::Generic(format!("invalid seed {seed}")))?,
+                    );
+                }
+                "--coordinator" => parsed.coordinator = Some(value()?),
+                "--worker" => parsed.worker = Some(value()?),
+                "--quiet" => parsed.quiet = true,
+                "--stats" => parsed.stats = Some(value()?.parse()?),
+                "--help" | "-h" => return Ok(None),
+                _ => return Err(Error::Generic(format!("unknown argument {arg}\n\n{USAGE}"))),
+            }
+        }
+
+        if !scenes::SCENES.iter().any(|(name, _)| *name == parsed.scene) {
+            let names: Vec<_> = scenes::SCENES.iter().map(|(name, _)| *name).collect();
+            return Err(Error::Generic(format!(
+                "unknown scene {}, expected one of {}",
+                parsed.scene,
+                names.join(", ")
+            )));
+        }
+
+        // a max below the default min lowers the min rather than being raised to it
+        parsed.adaptive = parsed.adaptive.map(|adaptive| {
+            AdaptiveSampling::new(
+                adaptive.min_samples.min(adaptive.max_samples),
+                adaptive.max_samples,
+                adaptive.threshold,
+            )
+        });
+
+        if parsed.coordinator.is_some() && (parsed.checkpoint.is_some() || parsed.resume) {
+            return Err(Error::Generic(String::from(
+                "--checkpoint and --resume only apply to renders on this machine, not --coordinator",
+            )));
+        }
+
+        if parsed.coordinator.is_some() && parsed.preview.is_some() {
+            return Err(Error::Generic(String::from(
+                "--preview only applies to renders on this machine, not --coordinator",
+            )));
+        }
+
+        if parsed.resume && parsed.checkpoint.is_none() {
+            return Err(Error::Generic(String::from(
+                "--resume needs the --checkpoint to resume from",
+            )));
+        }
+
+        if parsed.coordinator.is_some() && parsed.worker.is_some() {
+            return Err(Error::Generic(String::from(
+                "--coordinator and --worker can't be given together",
+            )));
+        }
+
+        Ok(Some(parsed))
+    }
+}
@@ -0,0 +1,386 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use crate::{
+    adaptive::AdaptiveSampling,
+    camera::Camera,
+    checkpoint::Persist,
+    film::{Film, Filter},
+    object::World,
+    prelude::*,
+    progress::{CancelToken, Progress, ProgressObserver},
+    projection::Projection,
+    sampler::Sampler,
+    scenes,
+    spectrum::Illuminant,
+    stats::Stats,
+    utils::rng,
+};
+
+const MAGIC: &[u8; 8] = b"rtdist01";
+const REGION_ROWS: u32 = 16; // rows of pixels handed to a worker at once
+const CONNECT_ATTEMPTS: u32 = 50; // workers may be started before the coordinator
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5); // while a worker's busy
+const WORKER_TIMEOUT: Duration = Duration::from_secs(30); // silence before a worker's given up on
+
+type Region = (u32, u32, u32, u32); // pixels x0..x1, y0..y1
+
+/// Everything the rendered image depends on, so a worker renders the same image as
+/// the coordinator and a resumed render the same as the one checkpointed. The scene is
+/// built again from its name and seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub scene: String,
+    pub seed: u64,
+    pub cryptomatte: Option<usize>, // ranks of the mattes, if they're kept
+    pub projection: Option<Projection>, // replaces the scene's projection
+    pub pass_samples: Option<u32>,  // samples per pixel in each pass
+    pub adaptive: Option<AdaptiveSampling>, // samples noisy pixels more
+    pub spectral: Option<Illuminant>, // traces spectral paths lit by it
+    pub sampler: Option<Sampler>,   // source of the random numbers of each sample
+    pub filter: Option<Filter>,     // reconstructs the image from the samples
+}
+
+impl Job {
+    /// Builds the job's scene from the same random numbers every time, with the
+    /// camera set up as the job asks
+    pub fn build(&self) -> Result<(Camera, World)> {
+        rng::seed(self.seed);
+        let (mut camera, world) = scenes::by_name(&self.scene)
+            .ok_or_else(|| Error::Generic(format!("unknown scene {}", self.scene)))?;
+        if let Some(ranks) = self.cryptomatte {
+            camera = camera.with_cryptomatte(ranks);
+        }
+        if let Some(projection) = self.projection {
+            camera = camera.with_projection(projection);
+        }
+        if let Some(pass_samples) = self.pass_samples {
+            camera = camera.with_pass_samples(pass_samples);
+        }
+        if let Some(adaptive) = self.adaptive {
+            camera = camera.with_adaptive_sampling(adaptive);
+        }
+        if let Some(illuminant) = self.spectral {
+            camera = camera.with_spectral(illuminant);
+        }
+        if let Some(sampler) = self.sampler {
+            // after adaptive sampling, which changes the samples a pixel takes
+            let sampler = sampler.for_samples(camera.max_samples());
+            camera = camera.with_sampler(sampler);
+        }
+        if let Some(filter) = self.filter {
+            camera = camera.with_filter(filter);
+        }
+        Ok((camera, world))
+    }
+}
+
+impl Persist for Job {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.scene.write(out)?;
+        self.seed.write(out)?;
+        self.cryptomatte.is_some().write(out)?;
+        (self.cryptomatte.unwrap_or_default() as u64).write(out)?;
+        self.projection.is_some().write(out)?;
+        self.projection.unwrap_or_default().write(out)?;
+        self.pass_samples.is_some().write(out)?;
+        self.pass_samples.unwrap_or_default().write(out)?;
+        self.adaptive.is_some().write(out)?;
+        self.adaptive.unwrap_or_default().write(out)?;
+        self.spectral.is_some().write(out)?;
+        self.spectral.unwrap_or_else(Illuminant::d65).write(out)?;
+        self.sampler.is_some().write(out)?;
+        self.sampler.unwrap_or_default().write(out)?;
+        self.filter.is_some().write(out)?;
+        self.filter.unwrap_or_default().write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        let scene = String::read(input)?;
+        let seed = u64::read(input)?;
+        let has_cryptomatte = bool::read(input)?;
+        let ranks = u64::read(input)? as usize;
+        let has_projection = bool::read(input)?;
+        let projection = Projection::read(input)?;
+        let has_pass_samples = bool::read(input)?;
+        let pass_samples = u32::read(input)?;
+        let has_adaptive = bool::read(input)?;
+        let adaptive = AdaptiveSampling::read(input)?;
+        let has_spectral = bool::read(input)?;
+        let spectral = Illuminant::read(input)?;
+        let has_sampler = bool::read(input)?;
+        let sampler = Sampler::read(input)?;
+        let has_filter = bool::read(input)?;
+        let filter = Filter::read(input)?;
+        Ok(Self {
+            scene,
+            seed,
+            cryptomatte: has_cryptomatte.then_some(ranks),
+            projection: has_projection.then_some(projection),
+            pass_samples: has_pass_samples.then_some(pass_samples),
+            adaptive: has_adaptive.then_some(adaptive),
+            spectral: has_spectral.then_some(spectral),
+            sampler: has_sampler.then_some(sampler),
+            filter: has_filter.then_some(filter),
+        })
+    }
+}
+
+/// Renders the image of `camera` by handing bands of rows to the workers connecting
+/// to `listener` and merging the samples they send back. Workers can come and go, the
+/// regions of a worker that drops out are handed to the others. Cancelling sends the
+/// workers home once they're done with their regions
+pub fn coordinate(
+    listener: TcpListener,
+    job: &Job,
+    camera: &Camera,
+    world: &World,
+    observer: &dyn ProgressObserver,
+    cancel: &CancelToken,
+) -> Result<Film> {
+    // polled so the coordinator stops listening once the image is done
+    listener.set_nonblocking(true)?;
+    eprintln!("Waiting for workers on {}", listener.local_addr()?);
+
+    let film = camera.film(world);
+    let (width, height) = (film.width(), film.height());
+    let regions: VecDeque<Region> = (0..height)
+        .step_by(REGION_ROWS as usize)
+        .map(|y0| (0, y0, width, (y0 + REGION_ROWS).min(height)))
+        .collect();
+
+    let total = regions.len();
+    let done = AtomicUsize::new(0);
+    let stats = Mutex::new(Stats::default());
+    let start = Instant::now();
+    let progress = |done: usize| Progress {
+        done: (done as u64 * (width * height) as u64) / total as u64,
+        total: (width * height) as u64,
+        stats: *stats.lock().unwrap(),
+        elapsed: start.elapsed(),
+    };
+    // adds up the events workers count rendering each region
+    let report = |done: usize, region_stats: &Stats| {
+        stats.lock().unwrap().merge(region_stats);
+        observer.on_progress(&progress(done));
+    };
+    let regions = Mutex::new(regions);
+    let film = Mutex::new(film);
+
+    thread::scope(|scope| -> Result<()> {
+        while done.load(Relaxed) < total && !cancel.is_cancelled() {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    info!("worker {peer} connected");
+                    let (regions, film, done, report) = (&regions, &film, &done, &report);
+                    scope.spawn(move || {
+                        let served =
+                            serve(stream, job, (regions, film, cancel), (done, total), report);
+                        if let Err(error) = served {
+                            warn!("worker {peer} dropped out: {error}");
+                        }
+                    });
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(POLL_INTERVAL)
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(())
+    })?;
+    observer.on_finish(&progress(total));
+
+    Ok(film.into_inner().unwrap())
+}
+
+// hands regions to a worker until every region is done, putting back the one it's
+// working on if the connection fails or the worker goes quiet
+fn serve(
+    stream: TcpStream,
+    job: &Job,
+    (regions, film, cancel): (&Mutex<VecDeque<Region>>, &Mutex<Film>, &CancelToken),
+    (done, total): (&AtomicUsize, usize),
+    report: impl Fn(usize, &Stats),
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(WORKER_TIMEOUT))?;
+    stream.set_write_timeout(Some(WORKER_TIMEOUT))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut out = BufWriter::new(stream);
+
+    out.write_all(MAGIC)?;
+    job.write(&mut out)?;
+    out.flush()?;
+
+    loop {
+        if cancel.is_cancelled() {
+            false.write(&mut out)?;
+            out.flush()?;
+            return Ok(());
+        }
+
+        let Some(region) = regions.lock().unwrap().pop_front() else {
+            // regions out with other workers may still come back
+            if done.load(Relaxed) == total {
+                false.write(&mut out)?;
+                out.flush()?;
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+
+        let (x0, y0, x1, y1) = region;
+        let mut tile = film.lock().unwrap().tile(x0, y0, x1, y1);
+        let rendered = (|| -> io::Result<Stats> {
+            true.write(&mut out)?;
+            [x0, y0, x1, y1]
+                .iter()
+                .try_for_each(|bound| bound.write(&mut out))?;
+            out.flush()?;
+            // heartbeats until the worker's done
+            while !bool::read(&mut input)? {}
+            tile.read_samples(&mut input)?;
+            Stats::read(&mut input)
+        })();
+
+        let region_stats = match rendered {
+            Ok(region_stats) => region_stats,
+            Err(error) => {
+                regions.lock().unwrap().push_back(region);
+                return Err(error.into());
+            }
+        };
+
+        film.lock().unwrap().merge(&tile);
+        report(done.fetch_add(1, Relaxed) + 1, &region_stats);
+    }
+}
+
+/// Connects to the coordinator at `address` and renders the regions it hands out
+/// until the image is done
+pub fn work(address: impl ToSocketAddrs) -> Result<()> {
+    let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+    let stream = connect(&addresses)?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut out = BufWriter::new(stream);
+
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(Error::Generic(String::from("not a render coordinator")));
+    }
+
+    let job = Job::read(&mut input)?;
+    let (camera, world) = heartbeat(&mut out, || job.build())??;
+    let film = camera.film(&world);
+
+    while bool::read(&mut input)? {
+        let region = (
+            u32::read(&mut input)?,
+            u32::read(&mut input)?,
+            u32::read(&mut input)?,
+            u32::read(&mut input)?,
+        );
+        info!("rendering region {region:?}");
+        let (tile, stats) = heartbeat(&mut out, || camera.render_region(&film, &world, region))?;
+        true.write(&mut out)?;
+        tile.write_samples(&mut out)?;
+        stats.write(&mut out)?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
+
+// runs `f` on another thread, telling the coordinator the worker's still there every
+// so often until it's done
+fn heartbeat<T: Send>(out: &mut impl Write, f: impl FnOnce() -> T + Send) -> io::Result<T> {
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        scope.spawn(move || sender.send(f()));
+        loop {
+            match receiver.recv_timeout(HEARTBEAT_INTERVAL) {
+                Ok(result) => return Ok(result),
+                Err(RecvTimeoutError::Timeout) => {
+                    false.write(out)?;
+                    out.flush()?;
+                }
+                // f panicked, which the scope passes on
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("worker thread panicked"))
+                }
+            }
+        }
+    })
+}
+
+fn connect(addresses: &[SocketAddr]) -> io::Result<TcpStream> {
+    let mut attempt = 1;
+    loop {
+        match TcpStream::connect(addresses) {
+            Ok(stream) => return Ok(stream),
+            Err(error) if attempt < CONNECT_ATTEMPTS => {
+                info!("couldn't reach the coordinator ({error}), retrying");
+                attempt += 1;
+                thread::sleep(POLL_INTERVAL * 2);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(film: &Film) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        film.write_samples(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn workers_render_the_same_image_as_a_local_render() {
+        // two samples a pixel keeps it quick
+        let job = Job {
+            scene: String::from("materials"),
+            seed: 7,
+            cryptomatte: None,
+            projection: None,
+            pass_samples: None,
+            adaptive: Some(AdaptiveSampling::new(2, 2, 0.)),
+            spectral: None,
+            sampler: None,
+            filter: None,
+        };
+        let (camera, world) = job.build().unwrap();
+        let local = samples(&camera.render(world));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (camera, world) = job.build().unwrap();
+        let film = thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(move || work(address).unwrap());
+            }
+            coordinate(listener, &job, &camera, &world, &(), &CancelToken::new()).unwrap()
+        });
+
+        assert!(samples(&film) == local);
+    }
+}
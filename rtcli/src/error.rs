@@ -3,5 +3,9 @@ pub enum Error {
     #[error("Generic {0}")]
     Generic(String),
     #[error(transparent)]
-    IO(#[from] std::io::Error)
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error(transparent)]
+    Exr(#[from] exr::error::Error)
 }
\ No newline at end of file
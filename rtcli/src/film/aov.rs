@@ -0,0 +1,267 @@
+use std::{path::Path, str::FromStr};
+
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+use image::{ImageBuffer, Rgb, Rgb32FImage};
+
+use crate::{adaptive, prelude::*, sampler, vec3::Color};
+
+use super::{
+    output::{self, OutputFormat},
+    Film,
+};
+
+/// Arbitrary output variables, passes written next to the beauty image for compositing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    Depth,       // distance from the camera to the first hit
+    Normal,      // world space normal of the first hit
+    Albedo,      // attenuation of the first hit
+    Position,    // world space point of the first hit
+    Uv,          // surface coordinates of the first hit
+    ObjectId,    // cryptomatte id of the object hit first, 0 for the background
+    MaterialId,  // cryptomatte id of the material hit first, 0 for the background
+    Direct,      // light reaching the camera after at most one bounce
+    Indirect,    // light reaching the camera after more bounces
+    SampleCount, // samples taken inside the pixel
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Position,
+        Aov::Uv,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::SampleCount,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::SampleCount => "samples",
+        }
+    }
+
+    // channel names of the pass in an exr layer
+    fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect => &["R", "G", "B"],
+            Aov::Uv => &["U", "V"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::SampleCount => &["count"],
+        }
+    }
+
+    // ids and counts are exact integers, which a float channel can't hold past 2^24
+    fn is_integer(self) -> bool {
+        matches!(self, Aov::ObjectId | Aov::MaterialId | Aov::SampleCount)
+    }
+}
+
+impl FromStr for Aov {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Aov::ALL
+            .into_iter()
+            .find(|aov| aov.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Aov::ALL.iter().map(|aov| aov.name()).collect();
+                Error::Generic(format!(
+                    "unknown aov '{s}', expected one of {}",
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+impl Film {
+    /// Value of a pass at a pixel, unused channels are zero
+    pub fn aov_value(&self, aov: Aov, x: u32, y: u32) -> [f32; 3] {
+        let features = self.features(x, y);
+        let color = |c: Color| [c.r(), c.g(), c.b()];
+        match aov {
+            Aov::Depth => [features.depth, 0., 0.],
+            Aov::Normal => [
+                features.normal.x(),
+                features.normal.y(),
+                features.normal.z(),
+            ],
+            Aov::Albedo => color(features.albedo),
+            Aov::Position => [
+                features.position.x(),
+                features.position.y(),
+                features.position.z(),
+            ],
+            Aov::Uv => [features.u, features.v, 0.],
+            Aov::ObjectId => [features.object_id as f32, 0., 0.],
+            Aov::MaterialId => [features.material_id as f32, 0., 0.],
+            Aov::Direct => color(features.direct),
+            Aov::Indirect => color(features.indirect),
+            Aov::SampleCount => [self.samples(x, y) as f32, 0., 0.],
+        }
+    }
+
+    // exact value of an integer pass at a pixel
+    fn aov_integer(&self, aov: Aov, x: u32, y: u32) -> u32 {
+        match aov {
+            Aov::ObjectId => self.features(x, y).object_id,
+            Aov::MaterialId => self.features(x, y).material_id,
+            Aov::SampleCount => self.samples(x, y),
+            _ => self.aov_value(aov, x, y)[0] as u32,
+        }
+    }
+
+    /// Raw values of a pass, single channel passes are repeated in every channel
+    pub fn aov_image(&self, aov: Aov) -> Rgb32FImage {
+        let single = aov.channels().len() == 1;
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let value = self.aov_value(aov, x, y);
+            Rgb(if single { [value[0]; 3] } else { value })
+        })
+    }
+
+    /// Pass mapped to [0, 1] for looking at: colour passes are exposed and tone mapped
+    /// like the beauty image, vectors and distances are normalised and ids get a
+    /// random colour each
+    pub fn aov_preview(&self, aov: Aov) -> Rgb32FImage {
+        let raw = self.aov_image(aov);
+        // largest magnitude of each channel, to normalise distances and counts with
+        let max = raw.pixels().fold([0f32; 3], |max, pixel| {
+            [0, 1, 2].map(|c| max[c].max(pixel.0[c].abs()))
+        });
+
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let [r, g, b] = raw.get_pixel(x, y).0;
+            let color = match aov {
+                Aov::Albedo | Aov::Direct | Aov::Indirect => {
+                    let exposed = Color::new(r, g, b) * 2f32.powf(self.exposure);
+                    self.tone_map.apply(exposed).to_srgb()
+                }
+                Aov::Normal => Color::new(r, g, b) * 0.5 + Color::new(0.5, 0.5, 0.5),
+                Aov::Position => {
+                    let scale = |v: f32, max: f32| 0.5 + 0.5 * v / max.max(f32::EPSILON);
+                    Color::new(scale(r, max[0]), scale(g, max[1]), scale(b, max[2]))
+                }
+                Aov::Depth => Color::new(r, r, r) * (1. / max[0].max(f32::EPSILON)),
+                Aov::Uv => Color::new(r, g, 0.),
+                Aov::ObjectId | Aov::MaterialId => id_color(self.aov_integer(aov, x, y)),
+                Aov::SampleCount => adaptive::heatmap(r / max[0].max(1.)).to_gamma(),
+            };
+            Rgb([color.r(), color.g(), color.b()])
+        })
+    }
+
+    /// Saves a heatmap of the samples taken in each pixel out of `max_samples`, showing
+    /// where adaptive sampling spent them
+    pub fn save_heatmap(&self, max_samples: u32, path: impl AsRef<Path>) -> Result<()> {
+        let heatmap = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let t = self.samples(x, y) as f32 / max_samples.max(1) as f32;
+            let color = adaptive::heatmap(t).to_gamma();
+            Rgb([color.r(), color.g(), color.b()])
+        });
+        output::write_display(&heatmap, &path, OutputFormat::from_path(&path)?)
+    }
+
+    /// Saves a pass as its own image, float formats get the raw values and
+    /// integer formats the preview
+    pub fn save_aov_as(
+        &self,
+        aov: Aov,
+        path: impl AsRef<Path>,
+        format: OutputFormat,
+    ) -> Result<()> {
+        match format {
+            OutputFormat::Exr => self.write_exr(false, &[aov], path),
+            format @ (OutputFormat::Hdr | OutputFormat::Pfm) => {
+                output::write_linear(&self.aov_image(aov), path, format)
+            }
+            format @ (OutputFormat::Png8 | OutputFormat::Png16) => {
+                output::write_display(&self.aov_preview(aov), path, format)
+            }
+        }
+    }
+
+    /// Saves the beauty image as the RGB channels of an exr with every pass as a
+    /// layer of channels named `<pass>.<channel>`, and the cryptomatte layers when the
+    /// film keeps them
+    pub fn save_layered_exr(&self, aovs: &[Aov], path: impl AsRef<Path>) -> Result<()> {
+        self.write_exr(true, aovs, path)
+    }
+
+    fn write_exr(&self, beauty: bool, aovs: &[Aov], path: impl AsRef<Path>) -> Result<()> {
+        let mut channels: SmallVec<[AnyChannel<FlatSamples>; 4]> = SmallVec::new();
+        let pixels = || (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)));
+
+        if beauty {
+            let image = self.to_rgb32f();
+            for (c, name) in ["R", "G", "B"].into_iter().enumerate() {
+                let samples = image.pixels().map(|pixel| pixel.0[c]).collect();
+                channels.push(AnyChannel::new(name, FlatSamples::F32(samples)));
+            }
+        }
+
+        for &aov in aovs {
+            for (c, channel) in aov.channels().iter().enumerate() {
+                // a lone pass keeps plain channel names, so `Z` is read as depth
+                let name = if beauty || aovs.len() > 1 {
+                    format!("{}.{channel}", aov.name())
+                } else {
+                    channel.to_string()
+                };
+                let samples = if aov.is_integer() {
+                    FlatSamples::U32(pixels().map(|(x, y)| self.aov_integer(aov, x, y)).collect())
+                } else {
+                    FlatSamples::F32(
+                        pixels()
+                            .map(|(x, y)| self.aov_value(aov, x, y)[c])
+                            .collect(),
+                    )
+                };
+                channels.push(AnyChannel::new(name.as_str(), samples));
+            }
+        }
+
+        let mut attributes = LayerAttributes::default();
+        if beauty {
+            let (mattes, metadata) = self.cryptomatte_channels();
+            channels.extend(mattes);
+            attributes.other.extend(metadata);
+        }
+
+        let layer = Layer::new(
+            (self.width as usize, self.height as usize),
+            attributes,
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        );
+        Image::from_layer(layer).write().to_file(path)?;
+        Ok(())
+    }
+}
+
+// distinct, stable colour for an id, black for the background
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::new(0., 0., 0.);
+    }
+    let [r, g, b, _] = sampler::hash(&[id]).to_le_bytes();
+    Color::new(r as f32 / 255., g as f32 / 255., b as f32 / 255.)
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use exr::prelude::{AnyChannel, AttributeValue, FlatSamples, Text};
+
+use crate::{
+    checkpoint::Persist,
+    object::{Hittable, World},
+};
+
+use super::{Film, FilmPixel, MIN_WEIGHT};
+
+/// Ids a pixel keeps the coverage of, the ones covering it least are dropped once
+/// there are more. Also the most ranks the mattes can have
+pub const MAX_IDS: usize = 16;
+
+// a matte's name, the names of its ids and how to pick its coverage out of a pixel
+type Matte<'a> = (&'a str, &'a [(String, u32)], fn(&FilmPixel) -> &Coverage);
+
+/// Id of a name the way cryptomatte hashes them, murmur3 with the exponent nudged so
+/// the bits read as a float are never inf, nan or denormal
+pub fn id(name: &str) -> u32 {
+    let hash = murmur3(name.as_bytes(), 0);
+    let exponent = (hash >> 23) & 0xff;
+    if exponent == 0 || exponent == 0xff {
+        hash ^ (1 << 23)
+    } else {
+        hash
+    }
+}
+
+// 32 bit murmur3 (x86 variant)
+fn murmur3(bytes: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut hash = seed;
+    let mut chunks = bytes.chunks_exact(4);
+    for chunk in &mut chunks {
+        let k = u32::from_le_bytes(chunk.try_into().unwrap());
+        hash = (hash ^ mix(k))
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .rev()
+            .fold(0u32, |k, &byte| (k << 8) | byte as u32);
+        hash ^= mix(k);
+    }
+
+    hash ^= bytes.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+/// Names behind the ids of a scene, written with the mattes so compositors can pick
+/// objects and materials by name
+#[derive(Debug, Clone)]
+pub struct Cryptomatte {
+    ranks: usize,                  // ids kept per pixel, the most covering first
+    objects: Vec<(String, u32)>,   // name and id of every object
+    materials: Vec<(String, u32)>, // name and id of every material
+}
+
+impl Cryptomatte {
+    /// Mattes of the objects and materials of `world`, keeping the `ranks` ids that
+    /// cover each pixel most
+    pub fn new(ranks: usize, world: &World) -> Self {
+        let ranks = ranks.min(MAX_IDS);
+        let mut materials = world.materials();
+        materials.sort_unstable_by_key(|(_, id)| *id);
+        materials.dedup_by_key(|(_, id)| *id);
+
+        Self {
+            ranks,
+            objects: world.object_names(),
+            materials,
+        }
+    }
+}
+
+/// Filter weights of the ids seen by the samples of a pixel, the `MAX_IDS` covering it
+/// most
+#[derive(Debug, Default, Clone)]
+pub(super) struct Coverage(Vec<(u32, f32)>);
+
+impl Coverage {
+    pub(super) fn add(&mut self, id: u32, weight: f32) {
+        if let Some((_, sum)) = self.0.iter_mut().find(|(other, _)| *other == id) {
+            *sum += weight;
+        } else if self.0.len() < MAX_IDS {
+            self.0.push((id, weight));
+        } else if let Some(least) = self.0.iter_mut().min_by(|a, b| a.1.total_cmp(&b.1)) {
+            // a new id only takes the place of the least covering one if it covers more
+            if weight > least.1 {
+                *least = (id, weight);
+            }
+        }
+    }
+
+    pub(super) fn merge(&mut self, other: &Coverage) {
+        for &(id, weight) in &other.0 {
+            self.add(id, weight);
+        }
+    }
+
+    // ids with the fraction of the pixel they cover, the most covering first
+    fn ranked(&self, total: f32) -> Vec<(u32, f32)> {
+        let scale = if total <= MIN_WEIGHT { 0. } else { 1. / total };
+        let mut ranked: Vec<_> = self
+            .0
+            .iter()
+            .map(|&(id, weight)| (id, weight * scale))
+            .collect();
+        ranked.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+impl Persist for Coverage {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.0.write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Vec::read(input).map(Coverage)
+    }
+}
+
+impl Film {
+    /// Keeps the ids seen through every pixel, to write cryptomatte layers with the
+    /// layered exr
+    pub fn with_cryptomatte(mut self, cryptomatte: Cryptomatte) -> Self {
+        self.cryptomatte = Some(cryptomatte);
+        self
+    }
+
+    // cryptomatte layers and header attributes for the object and material mattes,
+    // each layer holds two ranks as id, coverage pairs in RGBA
+    pub(super) fn cryptomatte_channels(
+        &self,
+    ) -> (Vec<AnyChannel<FlatSamples>>, HashMap<Text, AttributeValue>) {
+        let mut channels = Vec::new();
+        let mut attributes = HashMap::new();
+        let Some(cryptomatte) = &self.cryptomatte else {
+            return (channels, attributes);
+        };
+
+        let mattes: [Matte; 2] = [
+            ("CryptoObject", &cryptomatte.objects, |pixel| &pixel.objects),
+            ("CryptoMaterial", &cryptomatte.materials, |pixel| {
+                &pixel.materials
+            }),
+        ];
+
+        for (name, names, coverage) in mattes {
+            let ranked: Vec<_> = self
+                .pixels
+                .iter()
+                .map(|pixel| coverage(pixel).ranked(pixel.weight))
+                .collect();
+
+            // ranks are written in pairs, the unused half of a layer is left empty
+            for rank in 0..cryptomatte.ranks.div_ceil(2) * 2 {
+                let layer = format!("{name}{:02}", rank / 2);
+                let [id_channel, coverage_channel] = if rank % 2 == 0 {
+                    ["R", "G"]
+                } else {
+                    ["B", "A"]
+                };
+
+                let entry = |pixel: &Vec<(u32, f32)>| pixel.get(rank).copied().unwrap_or((0, 0.));
+                let ids = ranked.iter().map(|pixel| f32::from_bits(entry(pixel).0));
+                let coverages = ranked.iter().map(|pixel| entry(pixel).1);
+                channels.push(AnyChannel::new(
+                    format!("{layer}.{id_channel}").as_str(),
+                    FlatSamples::F32(ids.collect()),
+                ));
+                channels.push(AnyChannel::new(
+                    format!("{layer}.{coverage_channel}").as_str(),
+                    FlatSamples::F32(coverages.collect()),
+                ));
+            }
+
+            // metadata keys are the first 7 hex digits of the layer name's hash, the
+            // values are utf-8
+            let key = format!("{:08x}", murmur3(name.as_bytes(), 0));
+            let mut add = |field: &str, value: &str| {
+                attributes.insert(
+                    Text::from(format!("cryptomatte/{}/{field}", &key[..7]).as_str()),
+                    AttributeValue::Text(Text::from_slice_unchecked(value.as_bytes())),
+                );
+            };
+            add("name", name);
+            add("hash", "MurmurHash3_32");
+            add("conversion", "uint32_to_float32");
+            add("manifest", &manifest(names));
+        }
+
+        (channels, attributes)
+    }
+}
+
+// json object from each name to its id in hex
+fn manifest(names: &[(String, u32)]) -> String {
+    let entries: Vec<_> = names
+        .iter()
+        .map(|(name, id)| {
+            let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("\"{name}\":\"{id:08x}\"")
+        })
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur3_matches_reference_vectors() {
+        assert_eq!(murmur3(b"", 0), 0);
+        assert_eq!(murmur3(b"", 1), 0x514e_28b7);
+        assert_eq!(murmur3(b"hello", 0), 0x248b_fa47);
+        assert_eq!(
+            murmur3(b"The quick brown fox jumps over the lazy dog", 0),
+            0x2e4f_f723
+        );
+    }
+
+    #[test]
+    fn ids_are_finite_normal_floats() {
+        for i in 0..10_000 {
+            let id = f32::from_bits(id(&format!("object_{i}")));
+            assert!(id.is_normal() || id == 0., "{id} isn't a normal float");
+        }
+    }
+
+    #[test]
+    fn coverage_keeps_the_ids_covering_most() {
+        let mut coverage = Coverage::default();
+        for id in 0..MAX_IDS as u32 * 2 {
+            coverage.add(id, id as f32);
+        }
+        // a small weight of a new id doesn't push out one covering more
+        coverage.add(1000, 0.5);
+        coverage.add(MAX_IDS as u32 * 2 - 1, 1.);
+
+        let ranked = coverage.ranked(1.);
+        assert_eq!(ranked.len(), MAX_IDS);
+        assert_eq!(ranked[0], (MAX_IDS as u32 * 2 - 1, MAX_IDS as f32 * 2.));
+        assert!(ranked.iter().all(|&(id, _)| id >= MAX_IDS as u32));
+    }
+}
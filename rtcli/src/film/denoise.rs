@@ -0,0 +1,133 @@
+use rayon::prelude::*;
+
+use crate::vec3::Color;
+
+use super::{Features, Film};
+
+// keeps black albedos from dividing by zero when separating the illumination
+const ALBEDO_EPSILON: f32 = 1e-3;
+
+// 1d weights of the 5 tap b3 spline, from the centre out
+const KERNEL: [f32; 3] = [3. / 8., 1. / 4., 1. / 16.];
+
+/// Edge avoiding à-trous wavelet filter, blurring the illumination with a 5x5 kernel
+/// whose taps spread twice as far each iteration, weighted down where the colour,
+/// albedo, normal or depth of a neighbour differ so edges and textures stay sharp.
+/// Dammertz et al. - "Edge-Avoiding À-Trous Wavelet Transform for fast Global
+/// Illumination Filtering" (2010)
+#[derive(Debug, Clone, Copy)]
+pub struct Denoiser {
+    pub iterations: u32,   // passes, the kernel reaches 2^(iterations + 1) pixels
+    pub sigma_color: f32,  // tolerated colour difference, halved every pass
+    pub sigma_albedo: f32, // tolerated albedo difference
+    pub sigma_normal: f32, // tolerated normal difference
+    pub sigma_depth: f32,  // tolerated depth difference relative to the pixel's depth
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 0.5,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.3,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+impl Denoiser {
+    // weight of a neighbour from how much it differs from the centre pixel
+    fn edge_stop(
+        &self,
+        sigma_color: f32,
+        (color_p, features_p): (Color, &Features),
+        (color_q, features_q): (Color, &Features),
+    ) -> f32 {
+        let distance_sq = |a: Color, b: Color| {
+            let d = a - b;
+            d.r() * d.r() + d.g() * d.g() + d.b() * d.b()
+        };
+        // compress the hdr colours so bright pixels don't stop everything
+        let compress = |c: Color| c / (1. + c.luminance().max(0.));
+
+        let color = distance_sq(compress(color_p), compress(color_q)) / (sigma_color * sigma_color);
+        let albedo = distance_sq(features_p.albedo, features_q.albedo)
+            / (self.sigma_albedo * self.sigma_albedo);
+        let normal = (features_p.normal - features_q.normal).len_sq()
+            / (self.sigma_normal * self.sigma_normal);
+        let depth = (features_p.depth - features_q.depth).abs()
+            / (self.sigma_depth * features_p.depth.max(1e-3));
+
+        (-(color + albedo + normal + depth)).exp()
+    }
+}
+
+impl Film {
+    /// Replaces the image with its denoised version, only the illumination is blurred
+    /// so the albedo's texture detail is kept
+    pub fn denoise(&mut self, denoiser: &Denoiser) {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let features: Vec<Features> = self.pixels.iter().map(|pixel| pixel.features()).collect();
+
+        let separate = |color: Color, albedo: Color| {
+            Color::new(
+                color.r() / (albedo.r().max(0.) + ALBEDO_EPSILON),
+                color.g() / (albedo.g().max(0.) + ALBEDO_EPSILON),
+                color.b() / (albedo.b().max(0.) + ALBEDO_EPSILON),
+            )
+        };
+        let mut illumination: Vec<Color> = self
+            .pixels
+            .iter()
+            .zip(&features)
+            .map(|(pixel, features)| separate(pixel.color(), features.albedo))
+            .collect();
+
+        for iteration in 0..denoiser.iterations {
+            let step = 1i64 << iteration;
+            let sigma_color = denoiser.sigma_color / (1 << iteration) as f32;
+
+            illumination = (0..illumination.len())
+                .into_par_iter()
+                .map(|p| {
+                    let (x, y) = (p as i64 % width, p as i64 / width);
+                    let centre = (illumination[p], &features[p]);
+
+                    let mut sum = Color::new(0., 0., 0.);
+                    let mut total = 0.;
+                    for dy in -2..=2i64 {
+                        for dx in -2..=2i64 {
+                            let (qx, qy) = (x + dx * step, y + dy * step);
+                            if qx < 0 || qy < 0 || qx >= width || qy >= height {
+                                continue;
+                            }
+                            let q = (qy * width + qx) as usize;
+                            let neighbour = (illumination[q], &features[q]);
+
+                            let weight = KERNEL[dx.unsigned_abs() as usize]
+                                * KERNEL[dy.unsigned_abs() as usize]
+                                * denoiser.edge_stop(sigma_color, centre, neighbour);
+                            sum += illumination[q] * weight;
+                            total += weight;
+                        }
+                    }
+                    // the centre tap always has a positive weight
+                    sum / total
+                })
+                .collect();
+        }
+
+        for ((pixel, features), illumination) in
+            self.pixels.iter_mut().zip(&features).zip(illumination)
+        {
+            let albedo = features.albedo;
+            let color = Color::new(
+                illumination.r() * (albedo.r().max(0.) + ALBEDO_EPSILON),
+                illumination.g() * (albedo.g().max(0.) + ALBEDO_EPSILON),
+                illumination.b() * (albedo.b().max(0.) + ALBEDO_EPSILON),
+            );
+            pixel.sum = color * pixel.weight;
+        }
+    }
+}
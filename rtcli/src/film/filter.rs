@@ -0,0 +1,237 @@
+use core::f32::consts::PI;
+use std::{
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use crate::{checkpoint::Persist, prelude::*};
+
+/// Reconstruction filter weighting how much a sample contributes to the pixels around
+/// it, by its offset from their centres in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    // equal weight within the radius, 0.5 is a plain average of each pixel's samples
+    Box { radius: f32 },
+    // linear falloff to 0 at the radius
+    Tent { radius: f32 },
+    // gaussian with standard deviation sigma, shifted to reach 0 at the radius
+    Gaussian { radius: f32, sigma: f32 },
+    // cubic with negative lobes that sharpen. Mitchell & Netravali - "Reconstruction
+    // Filters in Computer Graphics" (1988), b = c = 1/3 is the recommended trade off
+    Mitchell { radius: f32, b: f32, c: f32 },
+    // sinc windowed by a wider sinc, sharpest but can ring around edges
+    Lanczos { radius: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    /// Weight of a sample `x`, `y` pixels away from a pixel centre
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        let radius = self.radius();
+        if x.abs() > radius || y.abs() > radius {
+            return 0.;
+        }
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    // all the filters are separable
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match *self {
+            Filter::Box { .. } => 1.,
+            Filter::Tent { radius } => (radius - x).max(0.),
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: f32| (-x * x / (2. * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                // the cubic is defined over [-2, 2]
+                let x = 2. * x / radius;
+                if x > 1. {
+                    ((-b - 6. * c) * x.powi(3)
+                        + (6. * b + 30. * c) * x.powi(2)
+                        + (-12. * b - 48. * c) * x
+                        + (8. * b + 24. * c))
+                        / 6.
+                } else {
+                    ((12. - 9. * b - 6. * c) * x.powi(3)
+                        + (-18. + 12. * b + 6. * c) * x.powi(2)
+                        + (6. - 2. * b))
+                        / 6.
+                }
+            }
+            Filter::Lanczos { radius } => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+// a tag for the variant followed by its parameters
+impl Persist for Filter {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        match *self {
+            Filter::Box { radius } => {
+                0u32.write(out)?;
+                radius.write(out)
+            }
+            Filter::Tent { radius } => {
+                1u32.write(out)?;
+                radius.write(out)
+            }
+            Filter::Gaussian { radius, sigma } => {
+                2u32.write(out)?;
+                radius.write(out)?;
+                sigma.write(out)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                3u32.write(out)?;
+                radius.write(out)?;
+                b.write(out)?;
+                c.write(out)
+            }
+            Filter::Lanczos { radius } => {
+                4u32.write(out)?;
+                radius.write(out)
+            }
+        }
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(match u32::read(input)? {
+            0 => Filter::Box {
+                radius: f32::read(input)?,
+            },
+            1 => Filter::Tent {
+                radius: f32::read(input)?,
+            },
+            2 => Filter::Gaussian {
+                radius: f32::read(input)?,
+                sigma: f32::read(input)?,
+            },
+            3 => Filter::Mitchell {
+                radius: f32::read(input)?,
+                b: f32::read(input)?,
+                c: f32::read(input)?,
+            },
+            4 => Filter::Lanczos {
+                radius: f32::read(input)?,
+            },
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown filter {tag}"),
+                ))
+            }
+        })
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    /// Parses `box`, `tent`, `gaussian`, `mitchell` or `lanczos` with an optional
+    /// `=radius` in pixels, 0.5, 1, 1.5, 2 and 3 by default. The gaussian falls off
+    /// with a sigma of a third of its radius and mitchell uses b = c = 1/3
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, parameter) = match s.split_once('=') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (s, None),
+        };
+        let radius = |default: f32| match parameter {
+            Some(radius) => radius
+                .parse::<f32>()
+                .ok()
+                .filter(|radius| *radius > 0.)
+                .ok_or_else(|| Error::Generic(format!("invalid filter radius {radius}"))),
+            None => Ok(default),
+        };
+
+        match name.to_ascii_lowercase().as_str() {
+            "box" => Ok(Filter::Box { radius: radius(0.5)? }),
+            "tent" => Ok(Filter::Tent { radius: radius(1.)? }),
+            "gaussian" => {
+                let radius = radius(1.5)?;
+                Ok(Filter::Gaussian {
+                    radius,
+                    sigma: radius / 3.,
+                })
+            }
+            "mitchell" => Ok(Filter::Mitchell {
+                radius: radius(2.)?,
+                b: 1. / 3.,
+                c: 1. / 3.,
+            }),
+            "lanczos" => Ok(Filter::Lanczos { radius: radius(3.)? }),
+            _ => Err(Error::Generic(format!(
+                "unknown filter {s}, expected box, tent, gaussian, mitchell or lanczos, each with an optional =radius"
+            ))),
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        return 1.;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_peak_in_the_middle_and_vanish_at_the_radius() {
+        for name in ["box", "tent", "gaussian", "mitchell", "lanczos"] {
+            let filter: Filter = name.parse().unwrap();
+            let radius = filter.radius();
+            let centre = filter.evaluate(0., 0.);
+            assert!(centre > 0.);
+
+            for i in 1..=16 {
+                let x = radius * i as f32 / 16.;
+                assert_eq!(filter.evaluate(x, 0.3), filter.evaluate(-x, -0.3));
+                assert_eq!(filter.evaluate(x, 0.3), filter.evaluate(0.3, x));
+                assert!(filter.evaluate(x, 0.) <= centre, "{name} at {x}");
+            }
+            assert!(filter.evaluate(radius * 1.01, 0.) == 0.);
+            assert!(filter.evaluate(0., radius * 1.01) == 0.);
+            if name != "box" {
+                assert!(filter.evaluate(radius, 0.).abs() < 1e-6, "{name} at its radius");
+            }
+        }
+    }
+
+    #[test]
+    fn parses_names_and_radii() {
+        assert_eq!("box".parse::<Filter>().unwrap(), Filter::Box { radius: 0.5 });
+        assert_eq!(
+            "Tent=2".parse::<Filter>().unwrap(),
+            Filter::Tent { radius: 2. }
+        );
+        assert_eq!(
+            "gaussian=3".parse::<Filter>().unwrap(),
+            Filter::Gaussian {
+                radius: 3.,
+                sigma: 1.
+            }
+        );
+        assert!("lanczos=0".parse::<Filter>().is_err());
+        assert!("lanczos=-1".parse::<Filter>().is_err());
+        assert!("sinc".parse::<Filter>().is_err());
+    }
+}
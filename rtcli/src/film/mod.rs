@@ -0,0 +1,473 @@
+use std::io::{self, Read, Write};
+
+use cryptomatte::Coverage;
+
+use crate::{
+    checkpoint::Persist,
+    object::HitRecord,
+    prelude::*,
+    ray::Ray,
+    vec3::{Color, Point3, Vec3},
+};
+
+pub mod aov;
+pub mod cryptomatte;
+pub mod denoise;
+pub mod filter;
+pub mod output;
+pub mod tone_map;
+pub use aov::Aov;
+pub use cryptomatte::Cryptomatte;
+pub use denoise::Denoiser;
+pub use filter::Filter;
+pub use output::OutputFormat;
+pub use tone_map::ToneMap;
+
+/// What a camera ray hit first and how its light splits up, kept alongside the colour
+/// to guide the denoiser and for the aov passes
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Features {
+    pub albedo: Color,    // attenuation of the first surface, or the background
+    pub normal: Vec3,     // world space normal, zero when the ray escaped
+    pub depth: f32,       // distance to the first hit, zero when the ray escaped
+    pub position: Point3, // world space point of the first hit
+    pub u: f32,           // surface coordinates of the first hit
+    pub v: f32,
+    pub direct: Color,    // light reaching the camera after at most one bounce
+    pub indirect: Color,  // the rest of the light
+    pub object_id: u32,   // ids of the first hit, 0 when the ray escaped,
+    pub material_id: u32, // these aren't averaged, a pixel keeps the nearest sample's
+}
+
+impl Features {
+    pub fn from_hit(ray: &Ray, record: &HitRecord, albedo: Color) -> Self {
+        Self {
+            albedo,
+            normal: record.normal,
+            depth: record.t * ray.direction().len(),
+            position: record.point,
+            u: record.u,
+            v: record.v,
+            object_id: record.object_id,
+            material_id: record.material_id,
+            ..Default::default()
+        }
+    }
+
+    fn add_weighted(&mut self, other: &Features, weight: f32) {
+        self.albedo += other.albedo * weight;
+        self.normal += other.normal * weight;
+        self.depth += other.depth * weight;
+        self.position += other.position * weight;
+        self.u += other.u * weight;
+        self.v += other.v * weight;
+        self.direct += other.direct * weight;
+        self.indirect += other.indirect * weight;
+    }
+}
+
+// least filter weight a pixel must gather to be coloured. Negative lobes can cancel
+// the weights of samples out to next to nothing, dividing by that would blow their
+// colour up, so pixels with less are left empty
+const MIN_WEIGHT: f32 = 1e-3;
+
+#[derive(Debug, Clone)]
+struct FilmPixel {
+    sum: Color,         // filter weighted sum of the samples
+    features: Features, // filter weighted sum of the samples' features
+    weight: f32,        // sum of the filter weights
+    samples: u32,       // samples taken inside the pixel
+    id_distance: f32,   // squared distance to the centre of the sample the ids are from
+    objects: Coverage,  // filter weights of the object ids, with cryptomatte enabled
+    materials: Coverage,
+}
+
+impl Default for FilmPixel {
+    fn default() -> Self {
+        Self {
+            sum: Color::default(),
+            features: Features::default(),
+            weight: 0.,
+            samples: 0,
+            id_distance: f32::INFINITY,
+            objects: Coverage::default(),
+            materials: Coverage::default(),
+        }
+    }
+}
+
+impl FilmPixel {
+    fn merge(&mut self, other: &FilmPixel) {
+        self.sum += other.sum;
+        self.features.add_weighted(&other.features, 1.);
+        self.weight += other.weight;
+        self.samples += other.samples;
+        if other.id_distance < self.id_distance {
+            self.id_distance = other.id_distance;
+            self.features.object_id = other.features.object_id;
+            self.features.material_id = other.features.material_id;
+        }
+        self.objects.merge(&other.objects);
+        self.materials.merge(&other.materials);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.weight <= MIN_WEIGHT
+    }
+
+    fn color(&self) -> Color {
+        if self.is_empty() {
+            return Color::new(0., 0., 0.);
+        }
+        self.sum * (1. / self.weight)
+    }
+
+    fn features(&self) -> Features {
+        let mut features = Features {
+            object_id: self.features.object_id,
+            material_id: self.features.material_id,
+            ..Default::default()
+        };
+        if !self.is_empty() {
+            features.add_weighted(&self.features, 1. / self.weight);
+        }
+        features
+    }
+}
+
+impl Persist for FilmPixel {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.sum.write(out)?;
+        self.features.write(out)?;
+        self.weight.write(out)?;
+        self.samples.write(out)?;
+        self.id_distance.write(out)?;
+        self.objects.write(out)?;
+        self.materials.write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            sum: Color::read(input)?,
+            features: Features::read(input)?,
+            weight: f32::read(input)?,
+            samples: u32::read(input)?,
+            id_distance: f32::read(input)?,
+            objects: Coverage::read(input)?,
+            materials: Coverage::read(input)?,
+        })
+    }
+}
+
+/// Float image the camera's samples are splatted onto, each sample is spread over
+/// the pixels within the radius of the reconstruction filter
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+    exposure: f32,                    // in stops, applied before tone mapping
+    tone_map: ToneMap,                // for writing displayable images
+    cryptomatte: Option<Cryptomatte>, // names of the ids the pixels' mattes are kept for
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Filter) -> Self {
+        Self {
+            width,
+            height,
+            filter,
+            pixels: vec![FilmPixel::default(); (width * height) as usize],
+            exposure: 0.,
+            tone_map: ToneMap::default(),
+            cryptomatte: None,
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Tile to splat the samples of pixels `x0..x1`, `y0..y1` onto, covering every
+    /// pixel the filter spreads them to. Tiles are filled independently, then merged
+    pub fn tile(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> FilmTile {
+        let reach = (self.filter.radius() - 0.5).max(0.).ceil() as u32;
+        let x0 = x0.saturating_sub(reach);
+        let y0 = y0.saturating_sub(reach);
+        let x1 = (x1 + reach).min(self.width);
+        let y1 = (y1 + reach).min(self.height);
+
+        FilmTile {
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            filter: self.filter,
+            mattes: self.cryptomatte.is_some(),
+            pixels: vec![FilmPixel::default(); ((x1 - x0) * (y1 - y0)) as usize],
+        }
+    }
+
+    pub fn merge(&mut self, tile: &FilmTile) {
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                let from = &tile.pixels[(y * tile.width + x) as usize];
+                self.pixels[((tile.y0 + y) * self.width + tile.x0 + x) as usize].merge(from);
+            }
+        }
+    }
+
+    /// Writes the samples splatted so far, all a checkpoint needs of the film
+    pub fn write_samples(&self, out: &mut impl Write) -> io::Result<()> {
+        self.width.write(out)?;
+        self.height.write(out)?;
+        self.pixels.write(out)
+    }
+
+    /// Film holding the samples written by `write_samples`, to carry on from with
+    /// `resume_from`
+    pub fn read_samples(input: &mut impl Read) -> io::Result<Self> {
+        let width = u32::read(input)?;
+        let height = u32::read(input)?;
+        let pixels: Vec<FilmPixel> = Vec::read(input)?;
+        // checked before the film is made, a corrupt size mustn't allocate or overflow
+        let size = u64::from(width).checked_mul(u64::from(height));
+        if width == 0 || height == 0 || size != Some(pixels.len() as u64) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "film pixels don't match its size",
+            ));
+        }
+        let mut film = Film::new(width, height, Filter::default());
+        film.pixels = pixels;
+        Ok(film)
+    }
+
+    /// Takes over the samples of `other`, which must be the same size
+    pub fn resume_from(&mut self, other: Film) -> Result<()> {
+        if (other.width, other.height) != (self.width, self.height) {
+            return Err(Error::Generic(format!(
+                "can't resume a {}x{} film from a {}x{} one",
+                self.width, self.height, other.width, other.height
+            )));
+        }
+        self.pixels = other.pixels;
+        Ok(())
+    }
+
+    /// Reconstructed colour of a pixel
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize].color()
+    }
+
+    /// Features of the first hits seen through a pixel
+    pub fn features(&self, x: u32, y: u32) -> Features {
+        self.pixels[(y * self.width + x) as usize].features()
+    }
+
+    /// Number of samples taken inside a pixel
+    pub fn samples(&self, x: u32, y: u32) -> u32 {
+        self.pixels[(y * self.width + x) as usize].samples
+    }
+
+    /// Pixel exposed, tone mapped and srgb encoded for display
+    pub fn display_pixel(&self, x: u32, y: u32) -> Color {
+        let exposed = self.pixel(x, y) * 2f32.powf(self.exposure);
+        self.tone_map.apply(exposed).to_srgb()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FilmTile {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    filter: Filter,
+    mattes: bool, // whether to keep the coverage of the ids
+    pixels: Vec<FilmPixel>,
+}
+
+impl FilmTile {
+    /// Adds the samples of a tile lying within this one, like a tile of the film
+    /// covering part of this tile's pixels
+    pub fn merge(&mut self, tile: &FilmTile) {
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                let from = &tile.pixels[(y * tile.width + x) as usize];
+                let (x, y) = (tile.x0 + x - self.x0, tile.y0 + y - self.y0);
+                self.pixels[(y * self.width + x) as usize].merge(from);
+            }
+        }
+    }
+
+    /// Writes the samples splatted onto the tile, to be read back into a tile of the
+    /// same pixels of another film
+    pub fn write_samples(&self, out: &mut impl Write) -> io::Result<()> {
+        self.x0.write(out)?;
+        self.y0.write(out)?;
+        self.width.write(out)?;
+        self.height.write(out)?;
+        self.pixels.write(out)
+    }
+
+    pub fn read_samples(&mut self, input: &mut impl Read) -> io::Result<()> {
+        let bounds = [
+            u32::read(input)?,
+            u32::read(input)?,
+            u32::read(input)?,
+            u32::read(input)?,
+        ];
+        let different = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "samples are of a different tile",
+            )
+        };
+        if bounds != [self.x0, self.y0, self.width, self.height] {
+            return Err(different());
+        }
+        let pixels: Vec<FilmPixel> = Vec::read(input)?;
+        if pixels.len() != self.pixels.len() {
+            return Err(different());
+        }
+        self.pixels = pixels;
+        Ok(())
+    }
+
+    /// Splats a sample taken at `position` in pixels on the film, pixel x, y spans
+    /// x..x+1, y..y+1
+    pub fn add_sample(&mut self, position: (f32, f32), color: Color, features: &Features) {
+        let radius = self.filter.radius();
+        let (px, py) = position;
+
+        // pixels with a centre inside the filter radius of the sample, half open so a
+        // sample on the edge of the radius lands in one pixel, the one it lies in for
+        // the box filter, and never past the reach of its tile
+        let range = |p: f32, start: u32, len: u32| {
+            let min = ((p - 0.5 - radius).floor() + 1.).max(start as f32) as u32;
+            let max = (p - 0.5 + radius).floor().min((start + len) as f32 - 1.);
+            min..(max + 1.).max(0.) as u32
+        };
+
+        for y in range(py, self.y0, self.height) {
+            for x in range(px, self.x0, self.width) {
+                let (dx, dy) = (x as f32 + 0.5 - px, y as f32 + 0.5 - py);
+                let weight = self.filter.evaluate(dx, dy);
+                let pixel = &mut self.pixels[((y - self.y0) * self.width + x - self.x0) as usize];
+                pixel.sum += color * weight;
+                pixel.features.add_weighted(features, weight);
+                pixel.weight += weight;
+
+                if px.floor() as u32 == x && py.floor() as u32 == y {
+                    pixel.samples += 1;
+                }
+                let distance = dx * dx + dy * dy;
+                if distance < pixel.id_distance {
+                    pixel.id_distance = distance;
+                    pixel.features.object_id = features.object_id;
+                    pixel.features.material_id = features.material_id;
+                }
+                // the background has no matte, it only dilutes the coverage of the rest
+                if self.mattes && features.object_id != 0 {
+                    pixel.objects.add(features.object_id, weight);
+                    pixel.materials.add(features.material_id, weight);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [&str; 5] = ["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+    fn largest(color: Color) -> f32 {
+        color.r().abs().max(color.g().abs()).max(color.b().abs())
+    }
+
+    #[test]
+    fn a_flat_image_comes_back_flat_through_every_filter() {
+        let color = Color::new(0.25, 0.5, 1.);
+        for name in FILTERS {
+            let mut film = Film::new(8, 8, name.parse().unwrap());
+            let mut tile = film.tile(0, 0, 8, 8);
+            for y in 0..32 {
+                for x in 0..32 {
+                    let position = ((x as f32 + 0.5) / 4., (y as f32 + 0.5) / 4.);
+                    tile.add_sample(position, color, &Features::default());
+                }
+            }
+            film.merge(&tile);
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    let pixel = film.pixel(x, y);
+                    assert!(
+                        largest(pixel - color) < 1e-4,
+                        "{name} filtered pixel {x}, {y} to {pixel:?}"
+                    );
+                    assert_eq!(film.samples(x, y), 16);
+                }
+            }
+        }
+    }
+
+    // offset along x of a sample whose weight cancels `weight`, between `near` and
+    // `far` where the filter falls monotonically below zero
+    fn cancelling(filter: Filter, weight: f32, mut near: f32, mut far: f32) -> f32 {
+        for _ in 0..64 {
+            let middle = (near + far) / 2.;
+            if filter.evaluate(middle, 0.) > -weight {
+                near = middle;
+            } else {
+                far = middle;
+            }
+        }
+        near
+    }
+
+    #[test]
+    fn negative_lobes_cancelling_out_leave_a_pixel_empty() {
+        // a bright sample on a positive lobe and a dark one on the negative lobe on the
+        // other side of the first pixel, their weights sum to next to nothing
+        let filters = [
+            ("mitchell".parse().unwrap(), 1.1, (1.15, 1.45)),
+            ("lanczos".parse().unwrap(), 2.5, (1., 1.4)),
+        ];
+        for (filter, positive, (near, far)) in filters {
+            let mut film = Film::new(8, 1, filter);
+            let mut tile = film.tile(0, 0, 8, 1);
+            let negative = cancelling(filter, filter.evaluate(positive, 0.), near, far);
+            let bright = Color::new(1., 1., 1.);
+            let features = Features {
+                albedo: bright,
+                ..Default::default()
+            };
+            tile.add_sample((0.5 + positive, 0.5), bright, &features);
+            tile.add_sample((0.5 - negative, 0.5), Color::default(), &Features::default());
+            film.merge(&tile);
+
+            let pixel = film.pixel(0, 0);
+            assert!(largest(pixel) == 0., "{filter:?} coloured the pixel {pixel:?}");
+            assert!(largest(film.features(0, 0).albedo) == 0.);
+        }
+    }
+}
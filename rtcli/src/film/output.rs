@@ -0,0 +1,151 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{ImageBuffer, ImageFormat, Rgb, Rgb32FImage};
+
+use crate::{prelude::*, vec3::Color};
+
+use super::Film;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Exr,   // openexr with 32 bit float channels, linear
+    Hdr,   // radiance rgbe, linear
+    Pfm,   // portable float map, linear
+    Png8,  // tone mapped and srgb encoded, 8 bits per channel
+    Png16, // tone mapped and srgb encoded, 16 bits per channel
+}
+
+impl OutputFormat {
+    /// Format for the file extension of `path`, png files are written with 8 bits
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("exr") => Ok(OutputFormat::Exr),
+            Some("hdr") => Ok(OutputFormat::Hdr),
+            Some("pfm") => Ok(OutputFormat::Pfm),
+            Some("png") => Ok(OutputFormat::Png8),
+            _ => Err(Error::Generic(format!(
+                "no output format for {}, expected exr, hdr, pfm or png",
+                path.as_ref().display()
+            ))),
+        }
+    }
+
+    /// The png format with `bits` per channel, 8 or 16, in place of either png format.
+    /// Other formats are kept as they are
+    pub fn with_png_depth(self, bits: u8) -> Self {
+        match (self, bits) {
+            (OutputFormat::Png8 | OutputFormat::Png16, 16) => OutputFormat::Png16,
+            (OutputFormat::Png8 | OutputFormat::Png16, _) => OutputFormat::Png8,
+            (format, _) => format,
+        }
+    }
+}
+
+impl Film {
+    /// Linear float copy of the reconstructed image
+    pub fn to_rgb32f(&self) -> Rgb32FImage {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let color = self.pixel(x, y);
+            Rgb([color.r(), color.g(), color.b()])
+        })
+    }
+
+    /// Exposed, tone mapped and srgb encoded copy of the reconstructed image
+    pub fn to_display(&self) -> Rgb32FImage {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let color = self.display_pixel(x, y);
+            Rgb([color.r(), color.g(), color.b()])
+        })
+    }
+
+    /// Saves the image in the format of the file extension of `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.save_as(&path, OutputFormat::from_path(&path)?)
+    }
+
+    pub fn save_as(&self, path: impl AsRef<Path>, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Exr | OutputFormat::Hdr | OutputFormat::Pfm => {
+                write_linear(&self.to_rgb32f(), path, format)
+            }
+            OutputFormat::Png8 | OutputFormat::Png16 => {
+                write_display(&self.to_display(), path, format)
+            }
+        }
+    }
+}
+
+/// Writes linear values to one of the float formats
+pub fn write_linear(
+    image: &Rgb32FImage,
+    path: impl AsRef<Path>,
+    format: OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Exr => image.save_with_format(path, ImageFormat::OpenExr)?,
+        OutputFormat::Hdr => image.save_with_format(path, ImageFormat::Hdr)?,
+        OutputFormat::Pfm => write_pfm(image, path)?,
+        OutputFormat::Png8 | OutputFormat::Png16 => {
+            return Err(Error::Generic(format!(
+                "{format:?} can't hold linear values, it needs a displayable image"
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Quantizes display encoded values in [0, 1] to one of the integer formats
+pub fn write_display(
+    image: &Rgb32FImage,
+    path: impl AsRef<Path>,
+    format: OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Png8 => {
+            let image: ImageBuffer<Rgb<u8>, Vec<u8>> =
+                ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                    let [r, g, b] = image.get_pixel(x, y).0;
+                    Rgb(Color::new(r, g, b).to_rgb())
+                });
+            image.save_with_format(path, ImageFormat::Png)?
+        }
+        OutputFormat::Png16 => {
+            let quantize = |c: f32| (c.clamp(0., 1.) * u16::MAX as f32).round() as u16;
+            let image: ImageBuffer<Rgb<u16>, Vec<u16>> =
+                ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                    Rgb(image.get_pixel(x, y).0.map(quantize))
+                });
+            image.save_with_format(path, ImageFormat::Png)?
+        }
+        OutputFormat::Exr | OutputFormat::Hdr | OutputFormat::Pfm => {
+            write_linear(image, path, format)?
+        }
+    }
+    Ok(())
+}
+
+// colour pfm, a text header with the size and a negative scale for little endian
+// followed by the rows from the bottom up
+fn write_pfm(image: &Rgb32FImage, path: impl AsRef<Path>) -> Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+    for y in (0..image.height()).rev() {
+        for x in 0..image.width() {
+            for channel in image.get_pixel(x, y).0 {
+                file.write_all(&channel.to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
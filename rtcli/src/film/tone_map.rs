@@ -0,0 +1,241 @@
+use std::str::FromStr;
+
+use crate::{prelude::*, vec3::Color};
+
+/// Operator compressing the linear radiance of the film into displayable [0, 1]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    // clip everything above 1
+    #[default]
+    Clamp,
+    // L / (1 + L) on the luminance, never quite reaching white
+    Reinhard,
+    // reinhard reaching white at a luminance of `white`
+    ExtendedReinhard {
+        white: f32,
+    },
+    // filmic curve fitted to the aces reference rendering transform by Stephen Hill
+    Aces,
+    // log encoding with a sigmoid in a desaturating working space, bright colours
+    // bleach towards white instead of clipping. Troy Sobotka's AgX, polynomial fit by
+    // Benjamin Wrensch
+    Agx,
+}
+
+impl ToneMap {
+    /// Maps a linear colour to linear display values, before the srgb encoding
+    pub fn apply(&self, color: Color) -> Color {
+        match *self {
+            ToneMap::Clamp => clamp(color),
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1. + l)),
+            ToneMap::ExtendedReinhard { white } => {
+                scale_luminance(color, |l| l * (1. + l / (white * white)) / (1. + l))
+            }
+            ToneMap::Aces => aces(color),
+            ToneMap::Agx => agx(color),
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = Error;
+
+    /// Parses `clamp`, `reinhard`, `reinhard-extended` with an optional `=white`
+    /// luminance, `aces` or `agx`
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, parameter) = match s.split_once('=') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (s, None),
+        };
+
+        match (name.to_ascii_lowercase().as_str(), parameter) {
+            ("clamp", None) => Ok(ToneMap::Clamp),
+            ("reinhard", None) => Ok(ToneMap::Reinhard),
+            ("reinhard-extended", white) => {
+                let white = match white {
+                    Some(white) => white
+                        .parse()
+                        .map_err(|_| Error::Generic(format!("invalid white point {white}")))?,
+                    None => 4.,
+                };
+                Ok(ToneMap::ExtendedReinhard { white })
+            }
+            ("aces", None) => Ok(ToneMap::Aces),
+            ("agx", None) => Ok(ToneMap::Agx),
+            _ => Err(Error::Generic(format!(
+                "unknown tone map {s}, expected clamp, reinhard, reinhard-extended[=white], aces or agx"
+            ))),
+        }
+    }
+}
+
+fn clamp(color: Color) -> Color {
+    Color::new(
+        color.r().clamp(0., 1.),
+        color.g().clamp(0., 1.),
+        color.b().clamp(0., 1.),
+    )
+}
+
+// scales the colour so its luminance follows the curve, keeping the hue
+fn scale_luminance(color: Color, curve: impl Fn(f32) -> f32) -> Color {
+    let luminance = color.luminance();
+    if luminance <= 0. {
+        return Color::new(0., 0., 0.);
+    }
+    clamp(color * (curve(luminance) / luminance))
+}
+
+// row major matrix times colour
+fn transform(m: [[f32; 3]; 3], c: Color) -> Color {
+    let row = |r: [f32; 3]| r[0] * c.r() + r[1] * c.g() + r[2] * c.b();
+    Color::new(row(m[0]), row(m[1]), row(m[2]))
+}
+
+fn aces(color: Color) -> Color {
+    // linear srgb to the rrt's working space, with the odt's exposure folded in
+    const INPUT: [[f32; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f32; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let fit = |v: f32| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.432951) + 0.238081;
+        a / b
+    };
+
+    let c = transform(INPUT, color);
+    clamp(transform(
+        OUTPUT,
+        Color::new(fit(c.r()), fit(c.g()), fit(c.b())),
+    ))
+}
+
+#[allow(clippy::excessive_precision)] // the matrices to the digit as blender ships them
+fn agx(color: Color) -> Color {
+    const INSET: [[f32; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const OUTSET: [[f32; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    // range of the log encoding in stops around middle grey
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+
+    let sigmoid = |v: f32| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    };
+
+    let c = transform(INSET, color);
+    let c = transform(
+        OUTSET,
+        Color::new(sigmoid(c.r()), sigmoid(c.g()), sigmoid(c.b())),
+    );
+    // the curve produces display encoded values, so undo its 2.2 gamma
+    let linear = |v: f32| v.clamp(0., 1.).powf(2.2);
+    Color::new(linear(c.r()), linear(c.g()), linear(c.b()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMap; 5] = [
+        ToneMap::Clamp,
+        ToneMap::Reinhard,
+        ToneMap::ExtendedReinhard { white: 4. },
+        ToneMap::Aces,
+        ToneMap::Agx,
+    ];
+
+    fn grey(value: f32) -> Color {
+        Color::new(value, value, value)
+    }
+
+    #[test]
+    fn curves_rise_from_black_within_the_display_range() {
+        for tone_map in OPERATORS {
+            let black = tone_map.apply(grey(0.));
+            assert!(black.r().max(black.g()).max(black.b()) < 1e-3, "{tone_map:?}");
+
+            let mut previous = 0.;
+            for i in 1..=200 {
+                let value = tone_map.apply(grey(0.001 * 1.05f32.powi(i))).g();
+                assert!((0. ..=1.).contains(&value), "{tone_map:?} gave {value}");
+                assert!(value >= previous, "{tone_map:?} falls to {value}");
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn reinhard_curves() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(close(ToneMap::Reinhard.apply(grey(1.)).r(), 0.5));
+        assert!(close(ToneMap::Reinhard.apply(grey(3.)).r(), 0.75));
+
+        let extended = ToneMap::ExtendedReinhard { white: 4. };
+        assert!(close(extended.apply(grey(4.)).r(), 1.));
+        assert!(extended.apply(grey(3.9)).r() < 1.);
+
+        // the hue is kept, only the luminance is mapped
+        let color = ToneMap::Reinhard.apply(Color::new(0.2, 0.4, 0.8));
+        assert!(close(color.g() / color.r(), 2.) && close(color.b() / color.r(), 4.));
+    }
+
+    #[test]
+    fn filmic_curves_roll_off_highlights() {
+        for tone_map in [ToneMap::Aces, ToneMap::Agx] {
+            let grey = tone_map.apply(grey(0.18)).g();
+            assert!((0.05..0.4).contains(&grey), "{tone_map:?} middle grey is {grey}");
+            let white = tone_map.apply(Color::new(100., 100., 100.)).g();
+            assert!(white > 0.9, "{tone_map:?} highlights only reach {white}");
+        }
+
+        // agx bleaches a very bright saturated colour towards white
+        let red = ToneMap::Agx.apply(Color::new(100., 0., 0.));
+        assert!(red.g() > 0.1 && red.b() > 0.1, "{red:?}");
+    }
+
+    #[test]
+    fn parses_operators() {
+        assert_eq!("clamp".parse::<ToneMap>().unwrap(), ToneMap::Clamp);
+        assert_eq!("AgX".parse::<ToneMap>().unwrap(), ToneMap::Agx);
+        assert_eq!(
+            "reinhard-extended".parse::<ToneMap>().unwrap(),
+            ToneMap::ExtendedReinhard { white: 4. }
+        );
+        assert_eq!(
+            "reinhard-extended=8".parse::<ToneMap>().unwrap(),
+            ToneMap::ExtendedReinhard { white: 8. }
+        );
+        assert!("reinhard=2".parse::<ToneMap>().is_err());
+        assert!("reinhard-extended=bright".parse::<ToneMap>().is_err());
+        assert!("filmic".parse::<ToneMap>().is_err());
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        let encoded = |linear: f32| grey(linear).to_srgb().r();
+        assert!(encoded(0.) == 0.);
+        assert!((encoded(1.) - 1.).abs() < 1e-6);
+        assert!((encoded(0.18) - 0.4613).abs() < 1e-3);
+        // the linear segment meets the curve
+        assert!((encoded(0.0031308) - encoded(0.0031309)).abs() < 1e-5);
+    }
+}
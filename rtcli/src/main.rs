@@ -1,21 +1,167 @@
-#![allow(warnings)]
+mod adaptive;
 mod camera;
+mod checkpoint;
+mod cli;
+mod distributed;
 mod error;
+mod film;
 mod material;
 mod object;
 mod prelude;
+mod progress;
+mod projection;
 mod ray;
+mod sampler;
 mod scenes;
+mod spectrum;
+mod stats;
+mod texture;
 mod utils;
 mod vec3;
 
+use std::{ffi::OsStr, net::TcpListener, ops::ControlFlow, time::Instant};
+
+use checkpoint::{Checkpoint, Checkpointing};
+use distributed::Job;
+use film::OutputFormat;
+use progress::{CancelToken, ProgressBar};
+use stats::StatsReport;
 use vec3::Color;
 
 use crate::prelude::*;
 
 fn main() -> Result<()> {
-    // creating a PPM image
-    let imgbuf = scenes::large_scene();
-    imgbuf.save("image.png").unwrap();
+    let Some(args) = cli::Args::parse(std::env::args().skip(1))? else {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    };
+
+    // workers take the scene and everything else from the coordinator
+    if let Some(address) = &args.worker {
+        return distributed::work(address.as_str());
+    }
+
+    // a resumed render has to build the same scene, from the same random numbers
+    let resume = match &args.checkpoint {
+        Some(path) if args.resume => Some(Checkpoint::load(path)?),
+        _ => None,
+    };
+    let seed = match (args.seed, &resume) {
+        (Some(seed), _) => seed,
+        (None, Some(checkpoint)) => checkpoint.job.seed,
+        (None, None) => rand::random(),
+    };
+    let job = Job {
+        scene: args.scene.clone(),
+        seed,
+        cryptomatte: args.cryptomatte,
+        projection: args.projection,
+        pass_samples: args.pass_samples,
+        adaptive: args.adaptive,
+        spectral: args.spectral,
+        sampler: args.sampler,
+        filter: args.filter,
+    };
+    if let Some(checkpoint) = &resume {
+        if checkpoint.job != job {
+            return Err(Error::Generic(format!(
+                "the checkpoint is of a different render, {:?} rather than {job:?}",
+                checkpoint.job
+            )));
+        }
+    }
+
+    // the scene's camera settings, overridden by the ones given on the command line
+    let (mut camera, world) = job.build()?;
+    if let Some(exposure) = args.exposure {
+        camera = camera.with_exposure(exposure);
+    }
+    if let Some(tone_map) = args.tone_map {
+        camera = camera.with_tone_map(tone_map);
+    }
+    if let Some(path) = &args.checkpoint {
+        camera = camera.with_checkpoints(Checkpointing {
+            path: path.clone(),
+            interval: args.checkpoint_interval,
+            job: job.clone(),
+        });
+    }
+    if let Some(checkpoint) = resume {
+        camera = camera.with_resume(checkpoint)?;
+    }
+    let format = OutputFormat::from_path(&args.output)?.with_png_depth(args.png_depth);
+
+    // ctrl-c stops the render and saves what's done, a second one stops right away
+    let cancel = CancelToken::new();
+    camera = camera.with_cancel(cancel.clone());
+    let stop = cancel.clone();
+    ctrlc::set_handler(move || {
+        if stop.is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("\nStopping, ctrl-c again to quit without saving");
+        stop.cancel();
+    })
+    .map_err(|error| Error::Generic(error.to_string()))?;
+    // the progress bar unless it's quiet, then the statistics when asked for
+    let observer = || {
+        (
+            (!args.quiet).then(ProgressBar::new),
+            args.stats.map(StatsReport),
+        )
+    };
+    camera = camera.with_progress(observer());
+    let max_samples = camera.max_samples();
+
+    let mut film = match &args.coordinator {
+        Some(address) => {
+            let observer = observer();
+            let listener = TcpListener::bind(address.as_str())?;
+            distributed::coordinate(listener, &job, &camera, &world, &observer, &cancel)?
+        }
+        None => match &args.preview {
+            // the image so far after every pass, unless one was saved not long ago
+            Some(path) => {
+                let mut last_preview: Option<Instant> = None;
+                camera.render_progressive(world, |_, film| {
+                    if last_preview.is_none_or(|last| last.elapsed() >= args.preview_interval) {
+                        if let Err(error) = film.save(path) {
+                            eprintln!("\nFailed to save the preview {path:?}: {error}");
+                        }
+                        last_preview = Some(Instant::now());
+                    }
+                    ControlFlow::Continue(())
+                })
+            }
+            None => camera.render(world),
+        },
+    };
+    if let Some(denoiser) = args.denoise {
+        film.denoise(&denoiser);
+    }
+
+    // passes and mattes are layers of an exr output, other formats get files next to
+    // the image as <stem>.<pass>.<ext>
+    let sibling = |suffix: &str, extension: &OsStr| {
+        let mut name = args.output.file_stem().unwrap_or_default().to_owned();
+        name.push(format!(".{suffix}."));
+        name.push(extension);
+        args.output.with_file_name(name)
+    };
+    if format == OutputFormat::Exr && (!args.aovs.is_empty() || args.cryptomatte.is_some()) {
+        film.save_layered_exr(&args.aovs, &args.output)?;
+    } else {
+        film.save_as(&args.output, format)?;
+        let extension = args.output.extension().unwrap_or_default();
+        for &aov in &args.aovs {
+            film.save_aov_as(aov, sibling(aov.name(), extension), format)?;
+        }
+        if args.cryptomatte.is_some() {
+            film.save_layered_exr(&[], sibling("cryptomatte", OsStr::new("exr")))?;
+        }
+    }
+    if args.heatmap {
+        film.save_heatmap(max_samples, sibling("heatmap", OsStr::new("png")))?;
+    }
     Ok(())
 }
@@ -0,0 +1,171 @@
+use core::f32::consts::PI;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    utils::{math::deg_to_rad, rng::random_2d},
+    vec3::{Color, Vec3},
+};
+
+use super::{ParameterHash, Parameters, Scatter};
+
+/// Metal with different roughness along the surface tangent and bitangent, for brushed
+/// aluminium or the stretched highlights on hair. Uses the anisotropic GGX
+/// distribution sampled from its visible normals, Heitz - "Sampling the GGX
+/// Distribution of Visible Normals" (2018), with a schlick fresnel tinted by `albedo`.
+/// The tangent comes from the hit's `dpdu`, which only spheres fill in, on anything
+/// else the highlights stretch along an arbitrary direction around the normal
+#[derive(Debug, Clone)]
+pub struct AnisotropicMetallic {
+    albedo: Color, // reflectance at normal incidence
+    alpha_t: f32,  // ggx roughness along the tangent
+    alpha_b: f32,  // ggx roughness along the bitangent
+    rotation: f32, // rotation of the tangent around the normal in radians
+}
+
+impl AnisotropicMetallic {
+    /// `roughness_t` and `roughness_b` are perceptual roughnesses in 0..=1 along the
+    /// surface tangent and bitangent, `rotation` turns them around the normal in degrees
+    pub fn new(albedo: &Color, roughness_t: f32, roughness_b: f32, rotation: f32) -> Self {
+        // squared so the roughness feels linear, clamped so the lobe never becomes a delta
+        let alpha = |roughness: f32| (roughness * roughness).clamp(1e-3, 1.);
+        Self {
+            albedo: *albedo,
+            alpha_t: alpha(roughness_t),
+            alpha_b: alpha(roughness_b),
+            rotation: deg_to_rad(rotation),
+        }
+    }
+
+    // tangent frame of the hit turned by `rotation`
+    fn frame(&self, record: &HitRecord) -> (Vec3, Vec3, Vec3) {
+        let (tangent, bitangent, normal) = record.tangent_frame();
+        let (sin, cos) = self.rotation.sin_cos();
+        (
+            cos * tangent + sin * bitangent,
+            cos * bitangent - sin * tangent,
+            normal,
+        )
+    }
+
+    // microfacet normal distribution, in the local frame where z is the normal
+    fn distribution(&self, m: &Vec3) -> f32 {
+        let x = m.x() / self.alpha_t;
+        let y = m.y() / self.alpha_b;
+        let t = x * x + y * y + m.z() * m.z();
+        1. / (PI * self.alpha_t * self.alpha_b * t * t)
+    }
+
+    // smith's masking auxiliary function for a local direction
+    fn lambda(&self, w: &Vec3) -> f32 {
+        let x = self.alpha_t * w.x();
+        let y = self.alpha_b * w.y();
+        let tan2 = (x * x + y * y) / (w.z() * w.z());
+        0.5 * (-1. + (1. + tan2).sqrt())
+    }
+
+    fn fresnel(&self, cos: f32) -> Color {
+        let white = Color::new(1., 1., 1.);
+        self.albedo + (white - self.albedo) * (1. - cos.clamp(0., 1.)).powi(5)
+    }
+
+    // samples a visible microfacet normal for local view direction `view`
+    fn sample_normal(&self, view: &Vec3) -> Vec3 {
+        // stretch the view so the distribution becomes the hemispherical configuration
+        let view = Vec3::new(self.alpha_t * view.x(), self.alpha_b * view.y(), view.z()).unit();
+
+        let len_sq = view.x() * view.x() + view.y() * view.y();
+        let t1 = if len_sq > 0. {
+            Vec3::new(-view.y(), view.x(), 0.) / len_sq.sqrt()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = view.cross(&t1);
+
+        // sample the projected disk, warped towards the visible half
+        let (u, v) = random_2d();
+        let r = u.sqrt();
+        let phi = 2. * PI * v;
+        let p1 = r * phi.cos();
+        let p2 = r * phi.sin();
+        let s = 0.5 * (1. + view.z());
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * p2;
+
+        let normal = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * view;
+
+        // unstretch
+        Vec3::new(
+            self.alpha_t * normal.x(),
+            self.alpha_b * normal.y(),
+            normal.z().max(1e-6),
+        )
+        .unit()
+    }
+}
+
+// world direction into the local frame
+fn to_local(w: &Vec3, (tangent, bitangent, normal): (Vec3, Vec3, Vec3)) -> Vec3 {
+    Vec3::new(w.dot(&tangent), w.dot(&bitangent), w.dot(&normal))
+}
+
+impl Scatter for AnisotropicMetallic {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let frame = self.frame(record);
+        let view = to_local(&-ray.direction().unit(), frame);
+        if view.z() <= 0. {
+            return None;
+        }
+
+        let m = self.sample_normal(&view);
+        let incoming = 2. * view.dot(&m) * m - view;
+        if incoming.z() <= 0. {
+            return None;
+        }
+
+        let (tangent, bitangent, normal) = frame;
+        let direction = incoming.x() * tangent + incoming.y() * bitangent + incoming.z() * normal;
+        let scattered = Ray::new(record.point, direction).with_wavelength(ray.wavelength());
+
+        // visible normal sampling leaves fresnel times the masking-shadowing ratio G2 / G1
+        let g1 = 1. / (1. + self.lambda(&view));
+        let g2 = 1. / (1. + self.lambda(&view) + self.lambda(&incoming));
+        Some((scattered, self.fresnel(view.dot(&m)) * (g2 / g1)))
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let frame = self.frame(record);
+        let view = to_local(&-ray.direction().unit(), frame);
+        let incoming = to_local(&direction.unit(), frame);
+        if view.z() <= 0. || incoming.z() <= 0. {
+            return Color::new(0., 0., 0.);
+        }
+
+        let m = (view + incoming).unit();
+        let g2 = 1. / (1. + self.lambda(&view) + self.lambda(&incoming));
+        // brdf F D G2 / (4 cos_o cos_i), times cos_i
+        self.fresnel(view.dot(&m)) * (self.distribution(&m) * g2 / (4. * view.z()))
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        let frame = self.frame(record);
+        let view = to_local(&-ray.direction().unit(), frame);
+        let incoming = to_local(&direction.unit(), frame);
+        if view.z() <= 0. || incoming.z() <= 0. {
+            return 0.;
+        }
+
+        let m = (view + incoming).unit();
+        let g1 = 1. / (1. + self.lambda(&view));
+        // visible normal density, D_v(m) / (4 o.m) = G1 D / (4 cos_o)
+        g1 * self.distribution(&m) / (4. * view.z())
+    }
+}
+
+impl Parameters for AnisotropicMetallic {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.color(&self.albedo);
+        hash.float(self.alpha_t);
+        hash.float(self.alpha_b);
+        hash.float(self.rotation);
+    }
+}
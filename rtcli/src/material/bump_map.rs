@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    texture::{Lookup, Texture},
+    vec3::{Color, Vec3},
+};
+
+use super::{Material, ParameterHash, Parameters, Scatter};
+
+// uv offset for finite differencing the height texture
+const DELTA: f32 = 0.0005;
+
+/// Perturbs the shading normal of `base` as if the surface was displaced along its
+/// normal by a height texture
+#[derive(Debug, Clone)]
+pub struct BumpMapped {
+    base: Arc<Material>,
+    height: Texture,
+    scale: f32, // displacement in world units of a height of 1
+}
+
+impl BumpMapped {
+    #[allow(dead_code)] // height maps come from images, which no built-in scene ships
+    pub fn new(base: Material, height: Texture, scale: f32) -> Self {
+        Self {
+            base: Arc::new(base),
+            height,
+            scale,
+        }
+    }
+
+    fn perturb(&self, ray: &Ray, record: &HitRecord) -> HitRecord {
+        let height =
+            |u: f32, v: f32| self.scale * self.height.value(u, v, &record.point).luminance();
+        let h = height(record.u, record.v);
+        let dhdu = (height(record.u + DELTA, record.v) - h) / DELTA;
+        let dhdv = (height(record.u, record.v + DELTA) - h) / DELTA;
+
+        // partial derivatives of the displaced surface p + h(u, v) n, dropping the
+        // negligible change of the normal itself
+        let (tangent, bitangent, normal) = record.tangent_frame();
+        let dpdu = record.dpdu.len().max(DELTA) * tangent + dhdu * normal;
+        let dpdv = record.dpdv.len().max(DELTA) * bitangent + dhdv * normal;
+
+        let mut bumped = dpdu.cross(&dpdv).unit();
+        if bumped.dot(&normal) < 0. {
+            bumped = -bumped;
+        }
+
+        let mut record = record.clone();
+        // keep the geometric normal where the bump would turn the surface away from the ray
+        if ray.direction().dot(&bumped) < 0. {
+            record.normal = bumped;
+        }
+        record
+    }
+}
+
+impl Scatter for BumpMapped {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        self.base.scatter(ray, &self.perturb(ray, record))
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.base.is_dispersive()
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        self.base.eval(ray, &self.perturb(ray, record), direction)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        self.base.pdf(ray, &self.perturb(ray, record), direction)
+    }
+
+    fn opacity(&self, record: &HitRecord) -> f32 {
+        self.base.opacity(record)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.base
+            .scatter_spectral(ray, &self.perturb(ray, record), wavelengths)
+    }
+}
+
+impl Parameters for BumpMapped {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.material(&self.base);
+        self.height.hash_parameters(hash);
+        hash.float(self.scale);
+    }
+}
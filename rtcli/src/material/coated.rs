@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    utils::rng::random_float,
+    vec3::{Color, Vec3},
+};
+
+use super::{schlick_reflectance, Material, ParameterHash, Parameters, Scatter};
+
+/// Thin smooth dielectric layer over `base`, like varnish or a car's clearcoat.
+/// The coat reflects with its fresnel reflectance and otherwise lets the ray through
+/// to the base, absorbing along the way in and back out again. Leaving through the coat
+/// the ray is weighted by its fresnel transmittance once more, the part the coat
+/// reflects back down is dropped rather than bounced between the coat and the base.
+/// So the layer loses a little energy, mostly at grazing angles, but never adds any.
+#[derive(Debug, Clone)]
+pub struct Coated {
+    base: Arc<Material>,
+    refractive_index: f32,
+    tint: Color, // colour of the coat seen through once at normal incidence
+}
+
+impl Coated {
+    pub fn new(base: Material, refractive_index: f32) -> Self {
+        Self {
+            base: Arc::new(base),
+            refractive_index,
+            tint: Color::new(1., 1., 1.),
+        }
+    }
+
+    pub fn with_tint(mut self, tint: &Color) -> Self {
+        self.tint = *tint;
+        self
+    }
+
+    // transmittance of the coat for a path entering at `cos_in` and leaving at `cos_out`
+    fn transmittance(&self, cos_in: f32, cos_out: f32) -> Color {
+        // path length through the coat relative to normal incidence, using the angles
+        // after refracting into it
+        let refracted = |cos: f32| {
+            let sin2 = (1. - cos * cos) / (self.refractive_index * self.refractive_index);
+            (1. - sin2).max(1e-4).sqrt()
+        };
+        let length = 1. / refracted(cos_in) + 1. / refracted(cos_out);
+
+        let channel = |c: f32| c.max(0.).powf(length);
+        Color::new(
+            channel(self.tint.r()),
+            channel(self.tint.g()),
+            channel(self.tint.b()),
+        )
+    }
+
+    // fraction of the light scattered by the base towards `direction` that makes it out
+    // through the coat, light the base transmits below the surface never meets it
+    fn exiting(&self, record: &HitRecord, direction: &Vec3) -> f32 {
+        let cos_out = direction.unit().dot(&record.normal);
+        if cos_out <= 0. {
+            return 1.;
+        }
+        1. - schlick_reflectance(cos_out, 1. / self.refractive_index)
+    }
+
+    // specular reflection off the coat, if the ray isn't let through to the base
+    fn reflect(&self, ray: &Ray, record: &HitRecord, cos_theta: f32) -> Option<Ray> {
+        (schlick_reflectance(cos_theta, 1. / self.refractive_index) > random_float()).then(|| {
+            let reflected = ray.direction().unit().reflect(&record.normal);
+            Ray::new(record.point, reflected).with_wavelength(ray.wavelength())
+        })
+    }
+}
+
+impl Scatter for Coated {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        // the coat only sits on the outside of the surface
+        if !record.front_face {
+            return self.base.scatter(ray, record);
+        }
+
+        let cos_in = (-ray.direction().unit()).dot(&record.normal).clamp(0., 1.);
+        if let Some(reflected) = self.reflect(ray, record, cos_in) {
+            return Some((reflected, Color::new(1., 1., 1.)));
+        }
+
+        let (scattered, attenuation) = self.base.scatter(ray, record)?;
+        let cos_out = scattered.direction().unit().dot(&record.normal).abs();
+        let exiting = self.exiting(record, scattered.direction());
+        Some((
+            scattered,
+            exiting * attenuation * self.transmittance(cos_in, cos_out),
+        ))
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.base.is_dispersive()
+    }
+
+    // only the base's lobes, the coat's mirror reflection can't be evaluated
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        if !record.front_face {
+            return self.base.eval(ray, record, direction);
+        }
+
+        let cos_in = (-ray.direction().unit()).dot(&record.normal).clamp(0., 1.);
+        let cos_out = direction.unit().dot(&record.normal).abs();
+        let entering = 1. - schlick_reflectance(cos_in, 1. / self.refractive_index);
+        let exiting = self.exiting(record, direction);
+        entering
+            * exiting
+            * self.base.eval(ray, record, direction)
+            * self.transmittance(cos_in, cos_out)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        if !record.front_face {
+            return self.base.pdf(ray, record, direction);
+        }
+
+        let cos_in = (-ray.direction().unit()).dot(&record.normal).clamp(0., 1.);
+        let entering = 1. - schlick_reflectance(cos_in, 1. / self.refractive_index);
+        entering * self.base.pdf(ray, record, direction)
+    }
+
+    fn opacity(&self, record: &HitRecord) -> f32 {
+        self.base.opacity(record)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        if !record.front_face {
+            return self.base.scatter_spectral(ray, record, wavelengths);
+        }
+
+        let cos_in = (-ray.direction().unit()).dot(&record.normal).clamp(0., 1.);
+        if let Some(reflected) = self.reflect(ray, record, cos_in) {
+            return Some((reflected, SampledSpectrum::splat(1.)));
+        }
+
+        let (scattered, attenuation) = self.base.scatter_spectral(ray, record, wavelengths)?;
+        let cos_out = scattered.direction().unit().dot(&record.normal).abs();
+        let exiting = self.exiting(record, scattered.direction());
+        let transmittance =
+            SampledSpectrum::from_rgb(&self.transmittance(cos_in, cos_out), wavelengths);
+        Some((scattered, attenuation * transmittance * exiting))
+    }
+}
+
+impl Parameters for Coated {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.material(&self.base);
+        hash.float(self.refractive_index);
+        hash.color(&self.tint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Diffuse, utils::rng, vec3::Point3};
+
+    #[test]
+    fn clear_coat_on_white_reflects_what_its_fresnel_lets_out() {
+        rng::seed(5);
+        let white = Material::Diffuse(Diffuse::new(&Color::new(1., 1., 1.)));
+        let material = Material::Coated(Coated::new(white, 1.5));
+        let fresnel = |cos: f32| schlick_reflectance(cos, 1. / 1.5);
+
+        // share of the light the diffuse base scatters that gets out through the coat
+        let steps = 1000;
+        let exiting = (0..steps)
+            .map(|i| {
+                let cos = (i as f32 + 0.5) / steps as f32;
+                (1. - fresnel(cos)) * 2. * cos / steps as f32
+            })
+            .sum::<f32>();
+
+        let normal = Vec3::new(0., 0., 1.);
+        for cos_view in [0.05f32, 0.3, 0.7, 1.] {
+            let sin_view = (1. - cos_view * cos_view).sqrt();
+            let ray = Ray::new(Point3::new(0., 0., 0.), Vec3::new(sin_view, 0., -cos_view));
+            let record =
+                HitRecord::new(1., &ray, Point3::new(0., 0., 0.), normal, material.clone());
+
+            let samples = 100_000;
+            let reflected = (0..samples)
+                .filter_map(|_| material.scatter(&ray, &record))
+                .map(|(_, weight)| weight.r())
+                .sum::<f32>()
+                / samples as f32;
+            let expected = fresnel(cos_view) + (1. - fresnel(cos_view)) * exiting;
+            assert!(
+                (reflected - expected).abs() < 0.01,
+                "reflected {reflected} rather than {expected} at a view cosine of {cos_view}"
+            );
+        }
+    }
+}
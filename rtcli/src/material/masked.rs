@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    texture::{Lookup, Texture},
+    vec3::{Color, Vec3},
+};
+
+use super::{Material, ParameterHash, Parameters, Scatter};
+
+/// Cuts out parts of `base` with an opacity texture, black is fully cut out and white
+/// fully opaque. The cut out hits are skipped by the primitives so any ray, not
+/// just camera rays, passes through them.
+#[derive(Debug, Clone)]
+pub struct Masked {
+    base: Arc<Material>,
+    opacity: Texture,
+}
+
+impl Masked {
+    pub fn new(base: Material, opacity: Texture) -> Self {
+        Self {
+            base: Arc::new(base),
+            opacity,
+        }
+    }
+}
+
+impl Scatter for Masked {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        self.base.scatter(ray, record)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.base.is_dispersive()
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        self.base.eval(ray, record, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        self.base.pdf(ray, record, direction)
+    }
+
+    fn opacity(&self, record: &HitRecord) -> f32 {
+        let alpha = self
+            .opacity
+            .value(record.u, record.v, &record.point)
+            .luminance();
+        alpha.clamp(0., 1.) * self.base.opacity(record)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.base.scatter_spectral(ray, record, wavelengths)
+    }
+}
+
+impl Parameters for Masked {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.material(&self.base);
+        self.opacity.hash_parameters(hash);
+    }
+}
@@ -0,0 +1,207 @@
+use core::f32::consts::{FRAC_PI_2, PI};
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    object::HitRecord,
+    prelude::*,
+    ray::Ray,
+    vec3::{Color, Vec3},
+};
+
+use super::{
+    cosine_hemisphere_pdf, sample_cosine_hemisphere, ParameterHash, Parameters, Scatter,
+};
+
+// resolution of the half/difference angle parameterisation, phi_d only covers half a
+// turn thanks to reciprocity
+const THETA_H_RES: usize = 90;
+const THETA_D_RES: usize = 90;
+const PHI_D_RES: usize = 180;
+const SAMPLES: usize = THETA_H_RES * THETA_D_RES * PHI_D_RES;
+const HASHED_SAMPLES: usize = 64; // values of the table its name is hashed from
+
+// per channel scale of the stored values
+const SCALE: [f64; 3] = [1. / 1500., 1.15 / 1500., 1.66 / 1500.];
+
+/// Isotropic BRDF measured by the MERL database, tabulated over the half and
+/// difference angles. Matusik et al. - "A Data-Driven Reflectance Model" (2003)
+#[derive(Debug, Clone)]
+pub struct Measured {
+    brdf: Arc<[Color]>,
+}
+
+impl Measured {
+    /// Loads a MERL `.binary` file
+    #[allow(dead_code)] // the database isn't redistributable so no built-in scene uses it
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    // the contents of a `.binary` file, three dimensions then the table
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (header, data) = bytes.split_at(bytes.len().min(12));
+        let dimensions: Vec<usize> = header
+            .chunks_exact(4)
+            .map(|dim| i32::from_le_bytes(dim.try_into().unwrap()) as usize)
+            .collect();
+        if dimensions != [THETA_H_RES, THETA_D_RES, PHI_D_RES] {
+            return Err(Error::Generic(format!(
+                "measured BRDF has dimensions {dimensions:?}, expected [{THETA_H_RES}, {THETA_D_RES}, {PHI_D_RES}]"
+            )));
+        }
+        if data.len() != 3 * SAMPLES * 8 {
+            return Err(Error::Generic(format!(
+                "measured BRDF has {} bytes of data, expected {}",
+                data.len(),
+                3 * SAMPLES * 8
+            )));
+        }
+
+        // the channels are stored one after the other as doubles, negative values
+        // mark directions that were never measured
+        let channel = |c: usize, i: usize| {
+            let offset = (c * SAMPLES + i) * 8;
+            let value = f64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+            (value * SCALE[c]).max(0.) as f32
+        };
+        let brdf = (0..SAMPLES)
+            .map(|i| Color::new(channel(0, i), channel(1, i), channel(2, i)))
+            .collect();
+
+        Ok(Self { brdf })
+    }
+
+    // reflectance between the two directions pointing away from the surface
+    fn lookup(&self, incoming: &Vec3, outgoing: &Vec3, normal: &Vec3) -> Color {
+        let half = (*incoming + *outgoing).unit();
+        let cos_h = normal.dot(&half).clamp(-1., 1.);
+        let theta_h = cos_h.acos();
+
+        // frame around the normal with the tangent towards the half vector, any
+        // azimuth does as the material is isotropic
+        let tangent = half - cos_h * *normal;
+        let (tangent, bitangent) = if tangent.is_near_zero() {
+            normal.orthonormal_basis()
+        } else {
+            let tangent = tangent.unit();
+            (tangent, normal.cross(&tangent))
+        };
+
+        // rotate the incoming direction so the half vector becomes the pole
+        let (x, y, z) = (
+            incoming.dot(&tangent),
+            incoming.dot(&bitangent),
+            incoming.dot(normal),
+        );
+        let (sin_h, cos_h) = theta_h.sin_cos();
+        let diff = Vec3::new(x * cos_h - z * sin_h, y, x * sin_h + z * cos_h);
+        let theta_d = diff.z().clamp(-1., 1.).acos();
+        let phi_d = diff.y().atan2(diff.x()).rem_euclid(PI);
+
+        // continuous table coordinates, theta_h is stored with a square root spacing
+        // to resolve the specular peak
+        let h = (theta_h / FRAC_PI_2).max(0.).sqrt() * THETA_H_RES as f32 - 0.5;
+        let d = theta_d / FRAC_PI_2 * THETA_D_RES as f32 - 0.5;
+        let p = phi_d / PI * PHI_D_RES as f32 - 0.5;
+        self.interpolate(h, d, p)
+    }
+
+    // trilinear filtering, clamping the angles from the pole and wrapping phi_d
+    fn interpolate(&self, h: f32, d: f32, p: f32) -> Color {
+        let clamp = |x: f32, res: usize| (x.max(0.) as usize).min(res - 1);
+        let (h0, d0, p0) = (h.floor(), d.floor(), p.floor());
+        let (th, td, tp) = ((h - h0).clamp(0., 1.), (d - d0).clamp(0., 1.), p - p0);
+
+        let sample = |h: f32, d: f32, p: f32| {
+            let h = clamp(h, THETA_H_RES);
+            let d = clamp(d, THETA_D_RES);
+            let p = (p as i64).rem_euclid(PHI_D_RES as i64) as usize;
+            self.brdf[p + PHI_D_RES * (d + THETA_D_RES * h)]
+        };
+        let lerp = |a: Color, b: Color, t: f32| a * (1. - t) + b * t;
+        let along_p = |h: f32, d: f32| lerp(sample(h, d, p0), sample(h, d, p0 + 1.), tp);
+        let along_d = |h: f32| lerp(along_p(h, d0), along_p(h, d0 + 1.), td);
+        lerp(along_d(h0), along_d(h0 + 1.), th)
+    }
+}
+
+impl Scatter for Measured {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let direction = sample_cosine_hemisphere(&record.normal);
+        let scattered = Ray::new(record.point, direction).with_wavelength(ray.wavelength());
+
+        // the cosine of eval cancels with the pdf leaving pi
+        let brdf = self.lookup(&-ray.direction().unit(), &direction.unit(), &record.normal);
+        Some((scattered, brdf * PI))
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let direction = direction.unit();
+        let cos_theta = record.normal.dot(&direction);
+        if cos_theta <= 0. {
+            return Color::new(0., 0., 0.);
+        }
+        self.lookup(&-ray.direction().unit(), &direction, &record.normal) * cos_theta
+    }
+
+    fn pdf(&self, _ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        cosine_hemisphere_pdf(&record.normal, direction)
+    }
+}
+
+impl Parameters for Measured {
+    // an evenly spaced handful of the table rather than the whole of it
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        for color in self.brdf.iter().step_by(SAMPLES / HASHED_SAMPLES) {
+            hash.color(color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a merl file with the given dimensions and every value of each channel the same
+    fn merl(dimensions: [i32; 3], values: [f64; 3]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = dimensions.iter().flat_map(|d| d.to_le_bytes()).collect();
+        for value in values {
+            for _ in 0..SAMPLES {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let error = |bytes: &[u8]| Measured::from_bytes(bytes).unwrap_err().to_string();
+        assert!(error(&[]).contains("dimensions"));
+        assert!(error(&[90, 0, 0, 0, 90, 0]).contains("dimensions"));
+        assert!(error(&merl([90, 90, 360], [1.; 3])).contains("dimensions"));
+        assert!(error(&merl([-90, 90, 180], [1.; 3])).contains("dimensions"));
+
+        let mut short = merl([90, 90, 180], [1.; 3]);
+        short.truncate(short.len() - 8);
+        assert!(error(&short).contains("bytes of data"));
+        let mut long = merl([90, 90, 180], [1.; 3]);
+        long.push(0);
+        assert!(error(&long).contains("bytes of data"));
+
+        assert!(Measured::open("no/such/file.binary").is_err());
+    }
+
+    #[test]
+    fn reads_scaled_channels_and_clamps_unmeasured_ones() {
+        let measured = Measured::from_bytes(&merl([90, 90, 180], [1500., 1500., -1.])).unwrap();
+        let normal = Vec3::new(0., 0., 1.);
+        let incoming = Vec3::new(0.6, 0., 0.8);
+        let outgoing = Vec3::new(-0.28, 0.96, 0.);
+        let outgoing = (outgoing + normal).unit();
+
+        let brdf = measured.lookup(&incoming, &outgoing, &normal);
+        assert!((brdf.r() - 1.).abs() < 1e-5);
+        assert!((brdf.g() - 1.15).abs() < 1e-5);
+        assert!(brdf.b() == 0.);
+    }
+}
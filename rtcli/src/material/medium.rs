@@ -0,0 +1,92 @@
+use crate::{utils::rng::random_float, vec3::Color};
+
+use super::{ParameterHash, Parameters};
+
+/// Homogeneous participating medium filling the inside of a closed surface, with
+/// absorption and scattering coefficients per unit distance for each channel
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Medium {
+    sigma_a: Color,
+    sigma_s: Color,
+}
+
+/// Outcome of a ray travelling through a medium towards the boundary
+pub enum MediumEvent {
+    // the ray scattered `distance` along its direction
+    Scattered { distance: f32, weight: Color },
+    // the ray made it to the boundary
+    Escaped { weight: Color },
+}
+
+impl Medium {
+    pub fn new(sigma_a: &Color, sigma_s: &Color) -> Self {
+        Self {
+            sigma_a: *sigma_a,
+            sigma_s: *sigma_s,
+        }
+    }
+
+    // purely absorbing medium, like coloured glass
+    pub fn absorbing(sigma_a: &Color) -> Self {
+        Self::new(sigma_a, &Color::new(0., 0., 0.))
+    }
+
+    /// Medium from its single scattering albedo and the mean distance travelled between
+    /// interactions, both per channel
+    pub fn from_albedo(albedo: &Color, mean_free_path: &Color) -> Self {
+        let sigma_t = Color::new(
+            1. / mean_free_path.r(),
+            1. / mean_free_path.g(),
+            1. / mean_free_path.b(),
+        );
+        let sigma_s = *albedo * sigma_t;
+        Self::new(&(sigma_t - sigma_s), &sigma_s)
+    }
+
+    fn sigma_t(&self) -> Color {
+        self.sigma_a + self.sigma_s
+    }
+
+    // beer-lambert transmittance over `distance`
+    pub fn transmittance(&self, distance: f32) -> Color {
+        (-distance * self.sigma_t()).exp()
+    }
+
+    /// Samples where a ray `max_distance` away from the boundary next interacts with the
+    /// medium. The free flight distance is sampled for one channel picked at random
+    /// and weighted against all three, so chromatic media don't need a path per channel.
+    pub fn sample(&self, max_distance: f32) -> MediumEvent {
+        let sigma_t = self.sigma_t();
+        let channels = [sigma_t.r(), sigma_t.g(), sigma_t.b()];
+        let channel = channels[((random_float() * 3.) as usize).min(2)];
+
+        let distance = if channel > 0. {
+            -(1. - random_float()).ln() / channel
+        } else {
+            f32::INFINITY
+        };
+
+        let average = |c: Color| (c.r() + c.g() + c.b()) / 3.;
+        if distance < max_distance {
+            let transmittance = self.transmittance(distance);
+            let pdf = average(sigma_t * transmittance);
+            MediumEvent::Scattered {
+                distance,
+                weight: self.sigma_s * transmittance / pdf,
+            }
+        } else {
+            let transmittance = self.transmittance(max_distance);
+            let pdf = average(transmittance);
+            MediumEvent::Escaped {
+                weight: transmittance / pdf,
+            }
+        }
+    }
+}
+
+impl Parameters for Medium {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.color(&self.sigma_a);
+        hash.color(&self.sigma_s);
+    }
+}
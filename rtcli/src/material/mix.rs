@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    texture::{Lookup, SolidColor, Texture},
+    utils::rng::random_float,
+    vec3::{Color, Vec3},
+};
+
+use super::{Material, ParameterHash, Parameters, Scatter};
+
+/// Blends two materials by picking one per scatter, `weight` is the chance of picking
+/// `b` over `a`, so the blend conserves energy as long as both children do
+#[derive(Debug, Clone)]
+pub struct Mix {
+    a: Arc<Material>,
+    b: Arc<Material>,
+    weight: Texture,
+}
+
+impl Mix {
+    pub fn new(a: Material, b: Material, weight: f32) -> Self {
+        let weight = Color::new(weight, weight, weight);
+        Self::textured(a, b, Texture::SolidColor(SolidColor::new(&weight)))
+    }
+
+    pub fn textured(a: Material, b: Material, weight: Texture) -> Self {
+        Self {
+            a: Arc::new(a),
+            b: Arc::new(b),
+            weight,
+        }
+    }
+
+    fn weight(&self, record: &HitRecord) -> f32 {
+        self.weight
+            .value(record.u, record.v, &record.point)
+            .luminance()
+            .clamp(0., 1.)
+    }
+
+    fn choose(&self, record: &HitRecord) -> &Material {
+        if random_float() < self.weight(record) {
+            &self.b
+        } else {
+            &self.a
+        }
+    }
+}
+
+impl Scatter for Mix {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        self.choose(record).scatter(ray, record)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.a.is_dispersive() || self.b.is_dispersive()
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let weight = self.weight(record);
+        (1. - weight) * self.a.eval(ray, record, direction)
+            + weight * self.b.eval(ray, record, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        let weight = self.weight(record);
+        (1. - weight) * self.a.pdf(ray, record, direction)
+            + weight * self.b.pdf(ray, record, direction)
+    }
+
+    fn opacity(&self, record: &HitRecord) -> f32 {
+        let weight = self.weight(record);
+        (1. - weight) * self.a.opacity(record) + weight * self.b.opacity(record)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.choose(record)
+            .scatter_spectral(ray, record, wavelengths)
+    }
+}
+
+impl Parameters for Mix {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.material(&self.a);
+        hash.material(&self.b);
+        self.weight.hash_parameters(hash);
+    }
+}
@@ -0,0 +1,829 @@
+use core::f32::consts::PI;
+
+use enum_dispatch::enum_dispatch;
+
+pub mod anisotropic;
+pub mod bump_map;
+pub mod coated;
+pub mod masked;
+pub mod measured;
+pub mod medium;
+pub mod mix;
+pub mod normal_map;
+pub mod oren_nayar;
+pub mod sheen;
+pub mod subsurface;
+pub mod thin_film;
+pub use anisotropic::AnisotropicMetallic;
+pub use bump_map::BumpMapped;
+pub use coated::Coated;
+pub use masked::Masked;
+pub use measured::Measured;
+pub use medium::Medium;
+pub use mix::Mix;
+pub use normal_map::NormalMapped;
+pub use oren_nayar::OrenNayar;
+pub use sheen::Sheen;
+pub use subsurface::Subsurface;
+pub use thin_film::ThinFilm;
+
+use thin_film::choose_reflection;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    spectrum::{self, SampledSpectrum, SampledWavelengths, RGB_WAVELENGTHS, WAVELENGTH_D_LINE},
+    utils::{complex::Complex, rng::random_float},
+    vec3::{Color, Vec3},
+};
+
+#[enum_dispatch(Scatter, Parameters)]
+#[derive(Debug, Clone)]
+pub enum Material {
+    Diffuse,      // lambertian reflection
+    Metallic,     // angle of incident == angle of reflection + fuzz
+    Dielectric,   // using snell's law
+    Conductor,    // metal with a measured complex refractive index
+    NormalMapped, // base material with a tangent space normal map
+    BumpMapped,   // base material with a height texture
+    Masked,       // base material with an opacity texture cutting holes in it
+    Mix,          // stochastic blend of two materials
+    Coated,       // dielectric clearcoat layered over a base material
+    Subsurface,   // random walk through a scattering medium inside the surface
+    OrenNayar,    // rough diffuse reflection
+    Sheen,        // diffuse with a velvet sheen lobe for cloth
+    // metal with roughness stretched along the surface tangent
+    AnisotropicMetallic,
+    Measured, // tabulated brdf loaded from measurements
+}
+
+impl Material {
+    /// Name that's the same for materials with the same parameters across runs, the
+    /// variant followed by a hash of the parameters, e.g. `Diffuse_3f1c2a9b`. Tables
+    /// and images only have a few of their values hashed
+    pub fn name(&self) -> String {
+        let mut hash = ParameterHash::default();
+        hash.material(self);
+        format!("{}_{:08x}", self.variant(), hash.0)
+    }
+
+    fn variant(&self) -> &'static str {
+        match self {
+            Material::Diffuse(_) => "Diffuse",
+            Material::Metallic(_) => "Metallic",
+            Material::Dielectric(_) => "Dielectric",
+            Material::Conductor(_) => "Conductor",
+            Material::NormalMapped(_) => "NormalMapped",
+            Material::BumpMapped(_) => "BumpMapped",
+            Material::Masked(_) => "Masked",
+            Material::Mix(_) => "Mix",
+            Material::Coated(_) => "Coated",
+            Material::Subsurface(_) => "Subsurface",
+            Material::OrenNayar(_) => "OrenNayar",
+            Material::Sheen(_) => "Sheen",
+            Material::AnisotropicMetallic(_) => "AnisotropicMetallic",
+            Material::Measured(_) => "Measured",
+        }
+    }
+}
+
+/// Hash of the parameters that tell materials apart, fnv-1a so it's the same across
+/// runs and platforms unlike the std hasher
+pub struct ParameterHash(u32);
+
+impl Default for ParameterHash {
+    fn default() -> Self {
+        Self(0x811c_9dc5)
+    }
+}
+
+impl ParameterHash {
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u32).wrapping_mul(0x0100_0193);
+        }
+    }
+
+    pub fn float(&mut self, value: f32) {
+        self.bytes(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn color(&mut self, color: &Color) {
+        for channel in [color.r(), color.g(), color.b()] {
+            self.float(channel);
+        }
+    }
+
+    // a material inside another, with its variant so different kinds of material
+    // with the same values don't hash the same
+    pub fn material(&mut self, material: &Material) {
+        self.bytes(material.variant().as_bytes());
+        material.hash_parameters(self);
+    }
+
+    pub fn optional(&mut self, value: Option<&impl Parameters>) {
+        self.bytes(&[value.is_some() as u8]);
+        if let Some(value) = value {
+            value.hash_parameters(self);
+        }
+    }
+}
+
+/// Values a material is told apart from others of its variant by, for its name. Chosen
+/// rather than everything the material holds, which can be whole tables and images
+#[enum_dispatch]
+pub trait Parameters {
+    fn hash_parameters(&self, hash: &mut ParameterHash);
+}
+
+#[enum_dispatch]
+pub trait Scatter {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)>;
+
+    // whether scattering depends on the wavelength the ray carries
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// BSDF times the cosine term for a ray arriving along `ray` and leaving along
+    /// `direction`, what light sampling needs to weigh a light from that direction.
+    /// Lobes that can't be evaluated, like perfect mirrors, return black.
+    fn eval(&self, _ray: &Ray, _record: &HitRecord, _direction: &Vec3) -> Color {
+        Color::new(0., 0., 0.)
+    }
+
+    // density `scatter` samples `direction` with, zero for lobes that can't be evaluated
+    fn pdf(&self, _ray: &Ray, _record: &HitRecord, _direction: &Vec3) -> f32 {
+        0.
+    }
+
+    // fraction of rays hitting the surface at `record` that are stopped by it
+    fn opacity(&self, _record: &HitRecord) -> f32 {
+        1.
+    }
+
+    /// Stochastic alpha test `Hittable::hit_opaque` skips cut out hits with, a hit with
+    /// fractional opacity is kept with that probability so the ray otherwise continues
+    fn alpha_test(&self, record: &HitRecord) -> bool {
+        let opacity = self.opacity(record);
+        opacity >= 1. || (opacity > 0. && random_float() < opacity)
+    }
+
+    // scatter evaluated at every wavelength of a spectral path,
+    // by default the rgb attenuation is upsampled to a spectrum
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.scatter(ray, record)
+            .map(|(scattered, attenuation)| {
+                (scattered, SampledSpectrum::from_rgb(&attenuation, wavelengths))
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diffuse {
+    albedo: Color,
+}
+
+impl Diffuse {
+    pub fn new(albedo: &Color) -> Self {
+        Self { albedo: *albedo }
+    }
+}
+
+impl Scatter for Diffuse {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let scatter_direction = sample_cosine_hemisphere(&record.normal);
+
+        let scattered = Ray::new(record.point, scatter_direction).with_wavelength(ray.wavelength());
+        Some((scattered, self.albedo))
+    }
+
+    fn eval(&self, _ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        self.albedo * cosine_hemisphere_pdf(&record.normal, direction)
+    }
+
+    fn pdf(&self, _ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        cosine_hemisphere_pdf(&record.normal, direction)
+    }
+}
+
+impl Parameters for Diffuse {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.color(&self.albedo);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Metallic {
+    albedo: Color, // color of the reflection/meterial
+    fuzz: f32,     // size of the radius of diffusion on the reflection
+}
+
+impl Metallic {
+    pub fn new(albedo: &Color, fuzz: f32) -> Self {
+        Self {
+            albedo: *albedo,
+            fuzz: fuzz.min(1.),
+        }
+    }
+}
+
+impl Scatter for Metallic {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let mut reflected = ray.direction().reflect(&record.normal);
+        reflected = reflected.unit() + self.fuzz * Vec3::random_unit();
+
+        let scattered = Ray::new(record.point, reflected).with_wavelength(ray.wavelength());
+
+        (scattered.direction().dot(&record.normal) > 0.).then_some((scattered, self.albedo))
+    }
+}
+
+impl Parameters for Metallic {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.color(&self.albedo);
+        hash.float(self.fuzz);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n(λ) = a + b / λ², λ in µm
+    Cauchy { a: f32, b: f32 },
+    // n(λ)² = 1 + Σ b_i λ² / (λ² - c_i), λ in µm
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    #[allow(clippy::excessive_precision)] // as printed in the schott catalogue
+    pub const CROWN_GLASS: Dispersion = Dispersion::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.],
+        c: [0.030625, 0.011236, 0.],
+    };
+
+    /// refractive index at `wavelength` given in nm
+    pub fn refractive_index(&self, wavelength: f32) -> f32 {
+        let l = wavelength / 1000.;
+        let l2 = l * l;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => (1. + (0..3)
+                .map(|i| b[i] * l2 / (l2 - c[i]))
+                .sum::<f32>())
+            .sqrt(),
+        }
+    }
+}
+
+impl Parameters for Dispersion {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        let (kind, coefficients) = match self {
+            Dispersion::Cauchy { a, b } => (0, vec![*a, *b]),
+            Dispersion::Sellmeier { b, c } => (1, [b.as_slice(), c.as_slice()].concat()),
+        };
+        hash.bytes(&[kind]);
+        for coefficient in coefficients {
+            hash.float(coefficient);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dielectric {
+    refractive_index: f32,          // index used when the ray carries no wavelength
+    dispersion: Option<Dispersion>, // wavelength dependent index
+    interior: Medium,               // absorbing medium inside the surface
+    thin_film: Option<ThinFilm>,    // coating on the outside of the surface
+}
+
+impl Dielectric {
+    pub fn new(refractive_index: f32) -> Self {
+        Self {
+            refractive_index,
+            dispersion: None,
+            interior: Medium::default(),
+            thin_film: None,
+        }
+    }
+
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        Self {
+            refractive_index: dispersion.refractive_index(WAVELENGTH_D_LINE),
+            dispersion: Some(dispersion),
+            interior: Medium::default(),
+            thin_film: None,
+        }
+    }
+
+    /// Tints the medium so that light travelling `distance` through it comes out as `color`
+    pub fn with_absorption(mut self, color: &Color, distance: f32) -> Self {
+        let coefficient = |c: f32| -c.max(1e-6).ln() / distance;
+        self.interior = Medium::absorbing(&Color::new(
+            coefficient(color.r()),
+            coefficient(color.g()),
+            coefficient(color.b()),
+        ));
+        self
+    }
+
+    /// Coats the surface with a thin film
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.thin_film = Some(film);
+        self
+    }
+
+    fn refractive_index(&self, wavelength: Option<f32>) -> f32 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.refractive_index(wavelength),
+            _ => self.refractive_index,
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, record: &HitRecord) -> Color {
+        // a back face hit means the ray has just crossed the medium, so attenuate it
+        // by the distance travelled inside
+        if record.front_face {
+            Color::new(1., 1., 1.)
+        } else {
+            let distance = record.t * ray.direction().len();
+            self.interior.transmittance(distance)
+        }
+    }
+
+    // reflects or refracts the ray, returning the scattered ray and its weight at each
+    // of `wavelengths`
+    fn interact<const N: usize>(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: [f32; N],
+    ) -> (Ray, [f32; N]) {
+        let refractive_index = self.refractive_index(ray.wavelength());
+        let ri = if record.front_face {
+            1. / refractive_index
+        } else {
+            refractive_index
+        };
+
+        let unit_direction = ray.direction().unit();
+
+        let cos_theta = (-unit_direction).dot(&record.normal);
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let cannot_refract = ri * sin_theta > 1.;
+
+        let (reflect, weight) = match self.thin_film {
+            // the film's reflectance differs per wavelength
+            Some(film) if !cannot_refract => {
+                let (outer, inner) = if record.front_face {
+                    (1., refractive_index)
+                } else {
+                    (refractive_index, 1.)
+                };
+                choose_reflection(
+                    wavelengths
+                        .map(|l| film.reflectance(cos_theta, outer, Complex::real(inner), l)),
+                )
+            }
+            // must reflect the ray when sin(Theta') > 1 or with schlick's reflectance approximation
+            _ => (
+                cannot_refract || schlick_reflectance(cos_theta, ri) > random_float(),
+                [1.; N],
+            ),
+        };
+
+        let direction = if reflect {
+            unit_direction.reflect(&record.normal)
+        } else {
+            unit_direction.refract(&record.normal, ri)
+        };
+        let scattered = Ray::new(record.point, direction).with_wavelength(ray.wavelength());
+
+        (scattered, weight)
+    }
+}
+
+impl Scatter for Dielectric {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let wavelengths = match ray.wavelength() {
+            Some(wavelength) => [wavelength; 3],
+            None => RGB_WAVELENGTHS,
+        };
+        let (scattered, [r, g, b]) = self.interact(ray, record, wavelengths);
+
+        Some((
+            scattered,
+            self.transmittance(ray, record) * Color::new(r, g, b),
+        ))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        let (scattered, weight) = self.interact(ray, record, *wavelengths.lambda());
+
+        let transmittance =
+            SampledSpectrum::from_rgb(&self.transmittance(ray, record), wavelengths);
+        Some((scattered, SampledSpectrum(weight) * transmittance))
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+}
+
+impl Parameters for Dielectric {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.float(self.refractive_index);
+        hash.optional(self.dispersion.as_ref());
+        self.interior.hash_parameters(hash);
+        hash.optional(self.thin_film.as_ref());
+    }
+}
+
+// cosine weighted direction in the hemisphere around unit vector `normal`
+pub fn sample_cosine_hemisphere(normal: &Vec3) -> Vec3 {
+    let direction = *normal + Vec3::random_unit();
+    if direction.is_near_zero() {
+        *normal
+    } else {
+        direction
+    }
+}
+
+// density of `sample_cosine_hemisphere` producing `direction`
+pub fn cosine_hemisphere_pdf(normal: &Vec3, direction: &Vec3) -> f32 {
+    normal.dot(&direction.unit()).max(0.) / PI
+}
+
+// schlick's approximation of the fresnel reflectance for the index ratio `ri`
+pub fn schlick_reflectance(cos: f32, ri: f32) -> f32 {
+    let mut r = (1. - ri) / (1. + ri);
+    r *= r;
+    r + (1. - r) * (1. - cos).powi(5)
+}
+
+/// Unpolarised fresnel reflectance of an interface into a medium with complex
+/// refractive index `eta` = n + ik, relative to the medium the ray travels in
+pub fn fresnel_complex(cos_theta: f32, eta: Complex) -> f32 {
+    let cos_i = Complex::real(cos_theta.clamp(0., 1.));
+    let sin2_i = Complex::real(1.) - cos_i * cos_i;
+    let sin2_t = sin2_i / (eta * eta);
+    let cos_t = (Complex::real(1.) - sin2_t).sqrt();
+
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel.norm_sq() + r_perpendicular.norm_sq()) / 2.
+}
+
+// complex refractive index sampled every 50nm from 400nm to 750nm
+#[derive(Debug, Clone, Copy)]
+pub struct ComplexIor {
+    n: &'static [f32],
+    k: &'static [f32],
+}
+
+impl ComplexIor {
+    // approximate measurements from Johnson & Christy (1972) and Rakić (1995)
+    pub const GOLD: ComplexIor = ComplexIor {
+        n: &[1.47, 1.40, 0.97, 0.43, 0.25, 0.17, 0.16, 0.16],
+        k: &[1.95, 1.88, 1.87, 2.45, 2.98, 3.47, 3.95, 4.40],
+    };
+    pub const SILVER: ComplexIor = ComplexIor {
+        n: &[0.17, 0.14, 0.13, 0.12, 0.12, 0.14, 0.14, 0.15],
+        k: &[1.95, 2.47, 2.92, 3.34, 3.73, 4.15, 4.52, 4.90],
+    };
+    pub const COPPER: ComplexIor = ComplexIor {
+        n: &[1.18, 1.17, 1.12, 1.02, 0.27, 0.21, 0.21, 0.24],
+        k: &[2.21, 2.40, 2.60, 2.58, 3.41, 3.67, 4.20, 4.60],
+    };
+    pub const ALUMINIUM: ComplexIor = ComplexIor {
+        n: &[0.49, 0.62, 0.77, 0.96, 1.20, 1.47, 1.83, 2.40],
+        k: &[4.86, 5.47, 6.08, 6.69, 7.26, 7.79, 8.31, 8.62],
+    };
+
+    pub fn at(&self, wavelength: f32) -> Complex {
+        Complex::new(
+            spectrum::interpolate(self.n, 400., 50., wavelength),
+            spectrum::interpolate(self.k, 400., 50., wavelength),
+        )
+    }
+}
+
+impl Parameters for ComplexIor {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        for &value in self.n.iter().chain(self.k) {
+            hash.float(value);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    ior: ComplexIor,             // refractive index of the metal
+    fuzz: f32,                   // size of the radius of diffusion on the reflection
+    thin_film: Option<ThinFilm>, // coating on top of the metal
+}
+
+impl Conductor {
+    pub fn new(ior: ComplexIor, fuzz: f32) -> Self {
+        Self {
+            ior,
+            fuzz: fuzz.min(1.),
+            thin_film: None,
+        }
+    }
+
+    /// Coats the metal with a thin film, like heat tinted titanium
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.thin_film = Some(film);
+        self
+    }
+
+    fn reflectance(&self, cos_theta: f32, wavelength: f32) -> f32 {
+        match self.thin_film {
+            Some(film) => film.reflectance(cos_theta, 1., self.ior.at(wavelength), wavelength),
+            None => fresnel_complex(cos_theta, self.ior.at(wavelength)),
+        }
+    }
+
+    fn reflect(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, f32)> {
+        let unit_direction = ray.direction().unit();
+        let cos_theta = (-unit_direction).dot(&record.normal);
+
+        let reflected = unit_direction.reflect(&record.normal) + self.fuzz * Vec3::random_unit();
+        let scattered = Ray::new(record.point, reflected).with_wavelength(ray.wavelength());
+
+        (scattered.direction().dot(&record.normal) > 0.).then_some((scattered, cos_theta))
+    }
+}
+
+impl Scatter for Conductor {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let (scattered, cos_theta) = self.reflect(ray, record)?;
+        let [r, g, b] = match ray.wavelength() {
+            Some(wavelength) => [self.reflectance(cos_theta, wavelength); 3],
+            None => RGB_WAVELENGTHS.map(|l| self.reflectance(cos_theta, l)),
+        };
+        Some((scattered, Color::new(r, g, b)))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        let (scattered, cos_theta) = self.reflect(ray, record)?;
+        let reflectance = SampledSpectrum::from_fn(wavelengths, |l| self.reflectance(cos_theta, l));
+        Some((scattered, reflectance))
+    }
+}
+
+impl Parameters for Conductor {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        self.ior.hash_parameters(hash);
+        hash.float(self.fuzz);
+        hash.optional(self.thin_film.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        texture::{SolidColor, Texture},
+        utils::rng,
+        vec3::Point3,
+    };
+
+    const SAMPLES: u32 = 200_000;
+    const BINS: usize = 8; // in the cosine to the normal and in the azimuth around it
+    const STEPS: usize = 8; // of the quadrature over each bin, in both directions
+
+    // a ray coming in at an angle onto a surface facing up
+    fn hit(material: &Material) -> (Ray, HitRecord) {
+        let ray = Ray::new(Point3::new(-1., 1., 0.3), Vec3::new(1., -1., -0.3).unit());
+        let normal = Vec3::new(0., 1., 0.);
+        let record = HitRecord::new(1., &ray, Point3::new(0., 0., 0.), normal, material.clone());
+        (ray, record)
+    }
+
+    // bin of a unit direction in the hemisphere around the frame's normal, by its
+    // cosine to the normal and azimuth around it
+    fn bin(direction: &Vec3, (tangent, bitangent, normal): (Vec3, Vec3, Vec3)) -> Option<usize> {
+        let cos_theta = direction.dot(&normal);
+        if cos_theta <= 0. {
+            return None;
+        }
+        let phi = direction.dot(&bitangent).atan2(direction.dot(&tangent)) + PI;
+        let i = ((cos_theta * BINS as f32) as usize).min(BINS - 1);
+        let j = ((phi / (2. * PI) * BINS as f32) as usize).min(BINS - 1);
+        Some(i * BINS + j)
+    }
+
+    // direction at the cosine and azimuth in the frame
+    fn in_frame(cos_theta: f32, phi: f32, (tangent, bitangent, normal): (Vec3, Vec3, Vec3)) -> Vec3 {
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let (x, y) = ((phi - PI).cos() * sin_theta, (phi - PI).sin() * sin_theta);
+        x * tangent + y * bitangent + cos_theta * normal
+    }
+
+    // checks that the directions `scatter` takes fall in each bin of the hemisphere as
+    // often as `pdf` integrated over the bin says. Materials with a mirror lobe `pdf`
+    // leaves out have the bin of the mirror direction skipped
+    fn assert_pdf_matches_sampling(material: Material, mirror_lobe: bool) {
+        rng::seed(1);
+        let (ray, record) = hit(&material);
+        let frame = record.tangent_frame();
+
+        let mut counts = [0u32; BINS * BINS];
+        for _ in 0..SAMPLES {
+            if let Some((scattered, _)) = material.scatter(&ray, &record) {
+                if let Some(bin) = bin(&scattered.direction().unit(), frame) {
+                    counts[bin] += 1;
+                }
+            }
+        }
+
+        let mirror = ray.direction().unit().reflect(&record.normal);
+        let skipped = mirror_lobe.then(|| bin(&mirror, frame)).flatten();
+
+        // bins are equal areas of the hemisphere, 1 / BINS of the cosine and 2 pi / BINS
+        // of the azimuth
+        let step = (1. / (BINS * STEPS) as f32, 2. * PI / (BINS * STEPS) as f32);
+        for i in 0..BINS {
+            for j in 0..BINS {
+                if skipped == Some(i * BINS + j) {
+                    continue;
+                }
+                let mut expected = 0.;
+                for k in 0..STEPS {
+                    for l in 0..STEPS {
+                        let cos_theta = ((i * STEPS + k) as f32 + 0.5) * step.0;
+                        let phi = ((j * STEPS + l) as f32 + 0.5) * step.1;
+                        let direction = in_frame(cos_theta, phi, frame);
+                        expected += material.pdf(&ray, &record, &direction) * step.0 * step.1;
+                    }
+                }
+
+                let sampled = counts[i * BINS + j] as f32 / SAMPLES as f32;
+                assert!(
+                    (sampled - expected).abs() < 0.002 + 0.03 * expected,
+                    "{material:?} bin ({i}, {j}) sampled {sampled} but the pdf gives {expected}"
+                );
+            }
+        }
+    }
+
+    // checks that the weight `scatter` returns is the material's eval over its pdf
+    fn assert_weight_is_eval_over_pdf(material: Material) {
+        rng::seed(2);
+        let (ray, record) = hit(&material);
+        for _ in 0..1000 {
+            let Some((scattered, weight)) = material.scatter(&ray, &record) else {
+                continue;
+            };
+            let direction = scattered.direction();
+            let pdf = material.pdf(&ray, &record, direction);
+            let expected = material.eval(&ray, &record, direction) / pdf;
+            let difference = weight - expected;
+            assert!(
+                difference.r().abs() + difference.g().abs() + difference.b().abs()
+                    < 1e-3 * (1. + expected.r() + expected.g() + expected.b()),
+                "{material:?} scattered with {weight:?} but eval / pdf gives {expected:?}"
+            );
+        }
+    }
+
+    fn materials() -> Vec<Material> {
+        let albedo = Color::new(0.8, 0.5, 0.2);
+        vec![
+            Material::Diffuse(Diffuse::new(&albedo)),
+            Material::OrenNayar(OrenNayar::new(&albedo, 30.)),
+            Material::Sheen(Sheen::new(&albedo, &Color::new(0.9, 0.9, 0.9), 0.4)),
+            Material::AnisotropicMetallic(AnisotropicMetallic::new(&albedo, 0.6, 0.9, 0.3)),
+        ]
+    }
+
+    #[test]
+    fn pdf_matches_sampling() {
+        for material in materials() {
+            assert_pdf_matches_sampling(material, false);
+        }
+        let diffuse = Material::Diffuse(Diffuse::new(&Color::new(0.8, 0.5, 0.2)));
+        let rough = Material::AnisotropicMetallic(AnisotropicMetallic::new(
+            &Color::new(0.9, 0.9, 0.9),
+            0.7,
+            0.5,
+            0.,
+        ));
+        assert_pdf_matches_sampling(Material::Mix(Mix::new(diffuse.clone(), rough, 0.3)), false);
+        assert_pdf_matches_sampling(Material::Coated(Coated::new(diffuse, 1.5)), true);
+    }
+
+    #[test]
+    fn absorption_follows_beer_lambert() {
+        let tint = Color::new(0.8, 0.5, 0.1);
+        let glass = Material::Dielectric(Dielectric::new(1.5).with_absorption(&tint, 2.));
+        let normal = Vec3::new(0., 0., 1.);
+
+        // leaving the glass head on after travelling `distance` through it
+        let through = |distance: f32| {
+            let ray = Ray::new(Point3::new(0., 0., -distance), normal);
+            let record = HitRecord::new(distance, &ray, Point3::default(), normal, glass.clone());
+            assert!(!record.front_face);
+            // at normal incidence schlick reflects 4% with a weight of 1 all the same
+            glass.scatter(&ray, &record).unwrap().1
+        };
+        let close = |a: Color, b: Color| {
+            let difference = a - b;
+            [difference.r(), difference.g(), difference.b()]
+                .iter()
+                .all(|d| d.abs() < 1e-5)
+        };
+
+        assert!(close(through(2.), tint));
+        assert!(close(through(4.), tint * tint));
+        assert!(close(through(0.), Color::new(1., 1., 1.)));
+        assert!(close(through(1.), Color::new(0.8f32.sqrt(), 0.5f32.sqrt(), 0.1f32.sqrt())));
+
+        // nothing's absorbed on the way in
+        let ray = Ray::new(Point3::new(0., 0., 1.), -normal);
+        let record = HitRecord::new(1., &ray, Point3::default(), normal, glass.clone());
+        assert!(close(glass.scatter(&ray, &record).unwrap().1, Color::new(1., 1., 1.)));
+    }
+
+    #[test]
+    fn dispersion_matches_measured_indices() {
+        // BK7 at the helium d line, diamond at the sodium d line
+        let crown = Dispersion::CROWN_GLASS.refractive_index(587.56);
+        assert!((crown - 1.5168).abs() < 1e-3, "crown glass {crown}");
+        let diamond = Dispersion::DIAMOND.refractive_index(589.3);
+        assert!((diamond - 2.417).abs() < 2e-3, "diamond {diamond}");
+
+        // blue bends more than red
+        for dispersion in [Dispersion::CROWN_GLASS, Dispersion::DIAMOND] {
+            assert!(dispersion.refractive_index(450.) > dispersion.refractive_index(650.));
+        }
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.01 };
+        assert!((cauchy.refractive_index(500.) - 1.54).abs() < 1e-5);
+    }
+
+    #[test]
+    fn wrappers_keep_the_opacity_of_their_base() {
+        let solid = |a: f32| Texture::SolidColor(SolidColor::new(&Color::new(a, a, a)));
+        let masked = || {
+            let diffuse = Material::Diffuse(Diffuse::new(&Color::new(0.5, 0.5, 0.5)));
+            Material::Masked(Masked::new(diffuse, solid(0.25)))
+        };
+        let wrappers = [
+            Material::Masked(Masked::new(masked(), solid(0.5))),
+            Material::BumpMapped(BumpMapped::new(masked(), solid(0.5), 1.)),
+            Material::NormalMapped(NormalMapped::new(masked(), solid(0.5))),
+            Material::Mix(Mix::new(masked(), masked(), 0.5)),
+            Material::Coated(Coated::new(masked(), 1.5)),
+        ];
+        let expected = [0.125, 0.25, 0.25, 0.25, 0.25];
+        for (material, expected) in wrappers.iter().zip(expected) {
+            let (_, record) = hit(material);
+            assert_eq!(material.opacity(&record), expected, "{}", material.name());
+        }
+    }
+
+    #[test]
+    fn names_follow_the_parameters() {
+        let diffuse = |r: f32| Material::Diffuse(Diffuse::new(&Color::new(r, 0.5, 0.5)));
+        assert_eq!(diffuse(0.5).name(), diffuse(0.5).name());
+        assert_ne!(diffuse(0.5).name(), diffuse(0.6).name());
+        assert!(diffuse(0.5).name().starts_with("Diffuse_"));
+
+        // the same values in another kind of material
+        let sheen = Material::Sheen(Sheen::new(
+            &Color::new(0.5, 0.5, 0.5),
+            &Color::new(0., 0., 0.),
+            0.5,
+        ));
+        let coated = |base: Material| Material::Coated(Coated::new(base, 1.5)).name();
+        assert_ne!(coated(diffuse(0.5)), coated(sheen));
+    }
+
+    #[test]
+    fn weight_is_eval_over_pdf() {
+        for material in materials() {
+            assert_weight_is_eval_over_pdf(material);
+        }
+    }
+}
\ No newline at end of file
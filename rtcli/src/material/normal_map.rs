@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    texture::{Lookup, Texture},
+    vec3::{Color, Vec3},
+};
+
+use super::{Material, ParameterHash, Parameters, Scatter};
+
+/// Perturbs the shading normal of `base` with a tangent space normal map,
+/// where red, green and blue map to the tangent, bitangent and normal
+#[derive(Debug, Clone)]
+pub struct NormalMapped {
+    base: Arc<Material>,
+    map: Texture,
+}
+
+impl NormalMapped {
+    #[allow(dead_code)] // normal maps come from images, which no built-in scene ships
+    pub fn new(base: Material, map: Texture) -> Self {
+        Self {
+            base: Arc::new(base),
+            map,
+        }
+    }
+
+    fn perturb(&self, ray: &Ray, record: &HitRecord) -> HitRecord {
+        let (tangent, bitangent, normal) = record.tangent_frame();
+        let texel = self.map.value(record.u, record.v, &record.point);
+        let local = Vec3::new(
+            2. * texel.r() - 1.,
+            2. * texel.g() - 1.,
+            2. * texel.b() - 1.,
+        );
+
+        let mut record = record.clone();
+        let mapped = (local.x() * tangent + local.y() * bitangent + local.z() * normal).unit();
+        // keep the geometric normal where the map would turn the surface away from the ray
+        if ray.direction().dot(&mapped) < 0. {
+            record.normal = mapped;
+        }
+        record
+    }
+}
+
+impl Scatter for NormalMapped {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        self.base.scatter(ray, &self.perturb(ray, record))
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.base.is_dispersive()
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        self.base.eval(ray, &self.perturb(ray, record), direction)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        self.base.pdf(ray, &self.perturb(ray, record), direction)
+    }
+
+    fn opacity(&self, record: &HitRecord) -> f32 {
+        self.base.opacity(record)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.base
+            .scatter_spectral(ray, &self.perturb(ray, record), wavelengths)
+    }
+}
+
+impl Parameters for NormalMapped {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.material(&self.base);
+        self.map.hash_parameters(hash);
+    }
+}
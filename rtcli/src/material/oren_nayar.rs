@@ -0,0 +1,90 @@
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    utils::math::deg_to_rad,
+    vec3::{Color, Vec3},
+};
+
+use super::{
+    cosine_hemisphere_pdf, sample_cosine_hemisphere, ParameterHash, Parameters, Scatter,
+};
+
+/// Rough diffuse surface made of lambertian v-cavities, which back scatters towards
+/// the light and looks flatter than `Diffuse`, like clay or the moon.
+/// Oren & Nayar - "Generalization of Lambert's Reflectance Model" (1994)
+#[derive(Debug, Clone)]
+pub struct OrenNayar {
+    albedo: Color,
+    a: f32, // terms of the qualitative model precomputed from the roughness
+    b: f32,
+}
+
+impl OrenNayar {
+    /// `roughness` is the standard deviation of the facet angles in degrees,
+    /// 0 is lambertian
+    pub fn new(albedo: &Color, roughness: f32) -> Self {
+        let sigma = deg_to_rad(roughness);
+        let sigma2 = sigma * sigma;
+        Self {
+            albedo: *albedo,
+            a: 1. - 0.5 * sigma2 / (sigma2 + 0.33),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    // reflectance scaling the lambertian term for the pair of directions
+    fn reflectance(&self, incoming: &Vec3, outgoing: &Vec3, normal: &Vec3) -> f32 {
+        let cos_i = normal.dot(incoming).clamp(0., 1.);
+        let cos_o = normal.dot(outgoing).clamp(0., 1.);
+        let sin_i = (1. - cos_i * cos_i).sqrt();
+        let sin_o = (1. - cos_o * cos_o).sqrt();
+
+        // cosine of the azimuth between the directions projected onto the surface
+        let tangent_i = *incoming - cos_i * *normal;
+        let tangent_o = *outgoing - cos_o * *normal;
+        let cos_phi = if tangent_i.is_near_zero() || tangent_o.is_near_zero() {
+            0.
+        } else {
+            tangent_i.unit().dot(&tangent_o.unit()).max(0.)
+        };
+
+        // sin(alpha) * tan(beta) with alpha the larger and beta the smaller angle
+        let sin_alpha_tan_beta = if cos_i > cos_o {
+            sin_o * sin_i / cos_i.max(1e-4)
+        } else {
+            sin_i * sin_o / cos_o.max(1e-4)
+        };
+
+        self.a + self.b * cos_phi * sin_alpha_tan_beta
+    }
+}
+
+impl Scatter for OrenNayar {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let direction = sample_cosine_hemisphere(&record.normal);
+        let scattered = Ray::new(record.point, direction).with_wavelength(ray.wavelength());
+
+        // the cosine and 1/pi of eval cancel with the pdf
+        let reflectance =
+            self.reflectance(&-ray.direction().unit(), &direction.unit(), &record.normal);
+        Some((scattered, self.albedo * reflectance))
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let direction = direction.unit();
+        let reflectance = self.reflectance(&-ray.direction().unit(), &direction, &record.normal);
+        self.albedo * reflectance * cosine_hemisphere_pdf(&record.normal, &direction)
+    }
+
+    fn pdf(&self, _ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        cosine_hemisphere_pdf(&record.normal, direction)
+    }
+}
+
+impl Parameters for OrenNayar {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.color(&self.albedo);
+        hash.float(self.a);
+        hash.float(self.b);
+    }
+}
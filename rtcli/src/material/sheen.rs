@@ -0,0 +1,165 @@
+use core::f32::consts::PI;
+
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    vec3::{Color, Vec3},
+};
+
+use super::{
+    cosine_hemisphere_pdf, sample_cosine_hemisphere, ParameterHash, Parameters, Scatter,
+};
+
+const ALBEDO_ANGLES: usize = 32; // view angles the sheen lobe's albedo is tabulated at
+
+/// Cloth like velvet or satin, a lambertian base with a sheen lobe from fibres
+/// catching light at grazing angles. The lobe uses the "Charlie" microfibre
+/// distribution with Ashikhmin's visibility term, Estevez & Kulla - "Production
+/// Friendly Microfacet Sheen BRDF" (2017). The base only gets the light the sheen
+/// doesn't reflect, so the two together never reflect more than comes in
+#[derive(Debug, Clone)]
+pub struct Sheen {
+    albedo: Color,  // colour of the diffuse base
+    sheen: Color,   // colour of the sheen lobe
+    roughness: f32, // spread of the fibres, from 0 exclusive to 1
+    lobe_albedo: [f32; ALBEDO_ANGLES], // light a white lobe reflects, by cosine of the view
+}
+
+impl Sheen {
+    pub fn new(albedo: &Color, sheen: &Color, roughness: f32) -> Self {
+        let roughness = roughness.clamp(0.07, 1.);
+        Self {
+            albedo: *albedo,
+            sheen: *sheen,
+            roughness,
+            lobe_albedo: std::array::from_fn(|i| {
+                Self::integrate_lobe(roughness, (i as f32 + 0.5) / ALBEDO_ANGLES as f32)
+            }),
+        }
+    }
+
+    // the sheen lobe of a white sheen for the pair of unit directions, without the
+    // cosine term
+    fn lobe(roughness: f32, incoming: &Vec3, outgoing: &Vec3, normal: &Vec3) -> f32 {
+        let cos_i = normal.dot(incoming);
+        let cos_o = normal.dot(outgoing);
+        if cos_i <= 0. || cos_o <= 0. {
+            return 0.;
+        }
+
+        let half = (*incoming + *outgoing).unit();
+        let cos_h = normal.dot(&half).clamp(0., 1.);
+        let sin_h = (1. - cos_h * cos_h).sqrt();
+
+        let inverse_roughness = 1. / roughness;
+        let distribution = (2. + inverse_roughness) * sin_h.powf(inverse_roughness) / (2. * PI);
+        let visibility = 1. / (4. * (cos_i + cos_o - cos_i * cos_o));
+        distribution * visibility
+    }
+
+    // fraction of the light from a view at `cos_view` to the normal the lobe reflects,
+    // by the midpoint rule over the cosine and azimuth of the other direction
+    fn integrate_lobe(roughness: f32, cos_view: f32) -> f32 {
+        const STEPS: usize = 32;
+        let normal = Vec3::new(0., 0., 1.);
+        let view = Vec3::new((1. - cos_view * cos_view).sqrt(), 0., cos_view);
+
+        let mut albedo = 0.;
+        for i in 0..STEPS {
+            let cos_theta = (i as f32 + 0.5) / STEPS as f32;
+            let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+            for j in 0..2 * STEPS {
+                let phi = PI * (j as f32 + 0.5) / STEPS as f32;
+                let direction = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                albedo += Self::lobe(roughness, &view, &direction, &normal) * cos_theta;
+            }
+        }
+        // each step covers 1 / STEPS of the cosine and pi / STEPS of the azimuth
+        (albedo * PI / (STEPS * STEPS) as f32).clamp(0., 1.)
+    }
+
+    // light the lobe reflects towards a view at `cos_view` to the normal, interpolated
+    // from the table
+    fn sheen_albedo(&self, cos_view: f32) -> f32 {
+        let x = (cos_view * ALBEDO_ANGLES as f32 - 0.5).clamp(0., (ALBEDO_ANGLES - 1) as f32);
+        let i = (x as usize).min(ALBEDO_ANGLES - 2);
+        let t = x - i as f32;
+        self.lobe_albedo[i] * (1. - t) + self.lobe_albedo[i + 1] * t
+    }
+
+    // brdf for the pair of unit directions, without the cosine term
+    fn brdf(&self, incoming: &Vec3, outgoing: &Vec3, normal: &Vec3) -> Color {
+        let cos_i = normal.dot(incoming);
+        if cos_i <= 0. || normal.dot(outgoing) <= 0. {
+            return Color::new(0., 0., 0.);
+        }
+
+        // the base is lit by what the sheen lets through
+        let sheen_albedo = self.sheen_albedo(cos_i);
+        let through = |sheen: f32| (1. - sheen * sheen_albedo).max(0.);
+        let base = Color::new(
+            through(self.sheen.r()),
+            through(self.sheen.g()),
+            through(self.sheen.b()),
+        );
+        let lobe = Self::lobe(self.roughness, incoming, outgoing, normal);
+        self.albedo * base / PI + self.sheen * lobe
+    }
+}
+
+impl Scatter for Sheen {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        let direction = sample_cosine_hemisphere(&record.normal);
+        let scattered = Ray::new(record.point, direction).with_wavelength(ray.wavelength());
+
+        let pdf = self.pdf(ray, record, &direction);
+        (pdf > 0.).then(|| (scattered, self.eval(ray, record, &direction) / pdf))
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let direction = direction.unit();
+        let brdf = self.brdf(&-ray.direction().unit(), &direction, &record.normal);
+        brdf * record.normal.dot(&direction).max(0.)
+    }
+
+    fn pdf(&self, _ray: &Ray, record: &HitRecord, direction: &Vec3) -> f32 {
+        cosine_hemisphere_pdf(&record.normal, direction)
+    }
+}
+
+impl Parameters for Sheen {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.color(&self.albedo);
+        hash.color(&self.sheen);
+        hash.float(self.roughness);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Material, utils::rng, vec3::Point3};
+
+    #[test]
+    fn white_sheen_reflects_no_more_than_comes_in() {
+        rng::seed(3);
+        let white = Color::new(1., 1., 1.);
+        let material = Material::Sheen(Sheen::new(&white, &white, 0.3));
+        let normal = Vec3::new(0., 0., 1.);
+        for cos_view in [0.05f32, 0.3, 0.7, 1.] {
+            let sin_view = (1. - cos_view * cos_view).sqrt();
+            let ray = Ray::new(Point3::new(0., 0., 0.), Vec3::new(sin_view, 0., -cos_view));
+            let record =
+                HitRecord::new(1., &ray, Point3::new(0., 0., 0.), normal, material.clone());
+
+            let samples = 100_000;
+            let reflected = (0..samples)
+                .filter_map(|_| material.scatter(&ray, &record))
+                .map(|(_, weight)| weight.r())
+                .sum::<f32>()
+                / samples as f32;
+            assert!(reflected < 1.01, "reflected {reflected} at a view cosine of {cos_view}");
+            assert!(reflected > 0.9, "lost {} at a view cosine of {cos_view}", 1. - reflected);
+        }
+    }
+}
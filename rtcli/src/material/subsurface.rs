@@ -0,0 +1,84 @@
+use crate::{
+    object::HitRecord,
+    ray::Ray,
+    utils::rng::random_float,
+    vec3::{Color, Vec3},
+};
+
+use super::{
+    medium::{Medium, MediumEvent},
+    schlick_reflectance, ParameterHash, Parameters, Scatter,
+};
+
+/// Translucent material like skin, wax or marble. Light refracts in through a smooth
+/// dielectric boundary and random walks through the scattering medium inside until it
+/// gets back out, so the surface has to be closed.
+///
+/// The walk happens one scatter at a time: a ray hitting the inside of the boundary has
+/// travelled through the medium from its origin, so it either scattered somewhere
+/// along the way or reached the boundary.
+///
+/// It has no base material of its own and is fully opaque, wrapped in `Masked` the
+/// holes let light in and out of the medium through them.
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    medium: Medium,
+    refractive_index: f32,
+}
+
+impl Subsurface {
+    pub fn new(albedo: &Color, mean_free_path: &Color, refractive_index: f32) -> Self {
+        Self {
+            medium: Medium::from_albedo(albedo, mean_free_path),
+            refractive_index,
+        }
+    }
+
+    // reflects or refracts through the boundary like a dielectric
+    fn cross_boundary(&self, ray: &Ray, record: &HitRecord) -> Ray {
+        let ri = if record.front_face {
+            1. / self.refractive_index
+        } else {
+            self.refractive_index
+        };
+
+        let unit_direction = ray.direction().unit();
+        let cos_theta = (-unit_direction).dot(&record.normal);
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+
+        let direction =
+            if ri * sin_theta > 1. || schlick_reflectance(cos_theta, ri) > random_float() {
+                unit_direction.reflect(&record.normal)
+            } else {
+                unit_direction.refract(&record.normal, ri)
+            };
+        Ray::new(record.point, direction).with_wavelength(ray.wavelength())
+    }
+}
+
+impl Scatter for Subsurface {
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+        if record.front_face {
+            return Some((self.cross_boundary(ray, record), Color::new(1., 1., 1.)));
+        }
+
+        let distance = record.t * ray.direction().len();
+        match self.medium.sample(distance) {
+            MediumEvent::Scattered { distance, weight } => {
+                // isotropic phase function
+                let point = *ray.origin() + distance * ray.direction().unit();
+                let scattered =
+                    Ray::new(point, Vec3::random_unit()).with_wavelength(ray.wavelength());
+                Some((scattered, weight))
+            }
+            MediumEvent::Escaped { weight } => Some((self.cross_boundary(ray, record), weight)),
+        }
+    }
+}
+
+impl Parameters for Subsurface {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        self.medium.hash_parameters(hash);
+        hash.float(self.refractive_index);
+    }
+}
@@ -0,0 +1,156 @@
+use core::f32::consts::PI;
+
+use crate::utils::{complex::Complex, rng::random_float};
+
+use super::{ParameterHash, Parameters};
+
+/// Thin transparent coating like a soap film or oil slick, light reflecting off its top
+/// and bottom interferes which gives wavelength dependent, iridescent reflectance
+#[derive(Debug, Clone, Copy)]
+pub struct ThinFilm {
+    thickness: f32,        // in nm
+    refractive_index: f32, // of the film itself
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, refractive_index: f32) -> Self {
+        Self {
+            thickness,
+            refractive_index,
+        }
+    }
+
+    /// Unpolarised reflectance at `wavelength` in nm of light arriving at `cos_theta` from
+    /// a medium with index `outer` onto the film over a substrate with index `substrate`,
+    /// using the airy summation of the reflections inside the film
+    pub fn reflectance(
+        &self,
+        cos_theta: f32,
+        outer: f32,
+        substrate: Complex,
+        wavelength: f32,
+    ) -> f32 {
+        let n1 = Complex::real(outer);
+        let n2 = Complex::real(self.refractive_index);
+        let n3 = substrate;
+
+        // snell's law through the film, cosines go complex past the critical angle
+        let cos1 = Complex::real(cos_theta.clamp(0., 1.));
+        let sin2_1 = Complex::real(1.) - cos1 * cos1;
+        let cos_in = |n: Complex| {
+            let ratio = n1 / n;
+            (Complex::real(1.) - ratio * ratio * sin2_1).sqrt()
+        };
+        let cos2 = cos_in(n2);
+        let cos3 = cos_in(n3);
+
+        // phase difference between successive reflections inside the film
+        let delta = Complex::real(4. * PI * self.thickness / wavelength) * n2 * cos2;
+        let phase = Complex::new(-delta.im, delta.re).exp();
+
+        let airy = |r12: Complex, r23: Complex| {
+            let r = (r12 + r23 * phase) / (Complex::real(1.) + r12 * r23 * phase);
+            r.norm_sq()
+        };
+
+        let s = airy(
+            (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+            (n2 * cos2 - n3 * cos3) / (n2 * cos2 + n3 * cos3),
+        );
+        let p = airy(
+            (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+            (n3 * cos2 - n2 * cos3) / (n3 * cos2 + n2 * cos3),
+        );
+        ((s + p) / 2.).clamp(0., 1.)
+    }
+}
+
+impl Parameters for ThinFilm {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        hash.float(self.thickness);
+        hash.float(self.refractive_index);
+    }
+}
+
+/// Picks reflection over transmission with the average of per wavelength reflectances,
+/// returning the choice and the weight it carries at each wavelength
+pub fn choose_reflection<const N: usize>(reflectance: [f32; N]) -> (bool, [f32; N]) {
+    let p = reflectance.iter().sum::<f32>() / N as f32;
+    if random_float() < p {
+        (true, reflectance.map(|r| r / p))
+    } else {
+        (false, reflectance.map(|r| (1. - r) / (1. - p)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rng;
+
+    // reflectance of glass at normal incidence, ((n1 - n2) / (n1 + n2))²
+    const BARE: f32 = 0.04;
+
+    fn glass(film: ThinFilm, cos_theta: f32, wavelength: f32) -> f32 {
+        film.reflectance(cos_theta, 1., Complex::real(1.5), wavelength)
+    }
+
+    #[test]
+    fn films_that_change_nothing_leave_the_bare_surface() {
+        for wavelength in [400., 550., 700.] {
+            // no thickness, or an index matching either side
+            for film in [
+                ThinFilm::new(0., 1.33),
+                ThinFilm::new(300., 1.5),
+                ThinFilm::new(300., 1.),
+            ] {
+                let r = glass(film, 1., wavelength);
+                assert!((r - BARE).abs() < 1e-4, "{film:?} reflects {r} at {wavelength}");
+            }
+        }
+    }
+
+    #[test]
+    fn quarter_and_half_wave_films() {
+        // a quarter wave coating with the geometric mean index cancels the reflection,
+        // a half wave one is as if it weren't there
+        let n = 1.5f32.sqrt();
+        let wavelength = 550.;
+        let quarter = ThinFilm::new(wavelength / (4. * n), n);
+        let half = ThinFilm::new(wavelength / (2. * n), n);
+        assert!(glass(quarter, 1., wavelength) < 1e-5);
+        assert!((glass(half, 1., wavelength) - BARE).abs() < 1e-4);
+
+        // other wavelengths aren't cancelled, which is where the colours come from
+        assert!(glass(quarter, 1., 400.) > 1e-3);
+    }
+
+    #[test]
+    fn grazing_light_is_reflected() {
+        let film = ThinFilm::new(300., 1.33);
+        assert!(glass(film, 0., 550.) > 0.999);
+        for i in 0..=20 {
+            let r = glass(film, i as f32 / 20., 550.);
+            assert!((0. ..=1.).contains(&r));
+        }
+    }
+
+    #[test]
+    fn choosing_a_reflection_is_unbiased() {
+        rng::seed(11);
+        let reflectance = [0.1, 0.5, 0.9];
+        let samples = 100_000;
+        let (mut reflected, mut transmitted) = ([0.; 3], [0.; 3]);
+        for _ in 0..samples {
+            let (reflect, weight) = choose_reflection(reflectance);
+            let sum = if reflect { &mut reflected } else { &mut transmitted };
+            for (sum, weight) in sum.iter_mut().zip(weight) {
+                *sum += weight / samples as f32;
+            }
+        }
+        for i in 0..3 {
+            assert!((reflected[i] - reflectance[i]).abs() < 0.01);
+            assert!((transmitted[i] - (1. - reflectance[i])).abs() < 0.01);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    material::{Material, Metallic},
+    material::{Material, Scatter},
     ray::Ray,
     utils::Interval,
     vec3::{Point3, Vec3},
@@ -18,11 +18,20 @@ pub struct HitRecord {
     pub t: f32,
     pub front_face: bool,
     pub material: Material,
+    pub u: f32, // surface coordinates of the hit
+    pub v: f32,
+    pub dpdu: Vec3, // partial derivatives of the surface point along u and v, zero but for spheres
+    pub dpdv: Vec3,
+    pub object_id: u32, // cryptomatte id of the top level object's name, 0 when unset
+    pub material_id: u32, // cryptomatte id of the material's name
 }
 
 impl HitRecord {
     /// sets the hit record
     /// BEWARE: `outward_normal` MUST be normalised
+    /// The surface coordinates and their derivatives `dpdu`, `dpdv` are left zeroed,
+    /// only `Sphere::hit` fills them in after. Other objects get the arbitrary tangent
+    /// frame of `tangent_frame` and no texture coordinates
     pub fn new(t: f32, ray: &Ray, point: Point3, outward_normal: Vec3, material: Material) -> Self {
         let front_face = ray.direction().dot(&outward_normal) < 0.;
         let normal = if front_face {
@@ -36,13 +45,63 @@ impl HitRecord {
             t,
             front_face,
             material,
+            u: 0.,
+            v: 0.,
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            object_id: 0,
+            material_id: 0,
         }
     }
+
+    /// Orthonormal shading frame (tangent, bitangent, normal) around `normal`.
+    /// The tangent follows dpdu and the bitangent the side dpdv points to, where the
+    /// parameterisation degenerates (e.g. the poles of a sphere) an arbitrary but
+    /// consistent tangent is used instead.
+    pub fn tangent_frame(&self) -> (Vec3, Vec3, Vec3) {
+        let normal = self.normal;
+        let tangent = self.dpdu - normal * normal.dot(&self.dpdu);
+
+        let (tangent, bitangent) = if tangent.is_near_zero() {
+            normal.orthonormal_basis()
+        } else {
+            let tangent = tangent.unit();
+            (tangent, normal.cross(&tangent))
+        };
+
+        let bitangent = if bitangent.dot(&self.dpdv) < 0. {
+            -bitangent
+        } else {
+            bitangent
+        };
+
+        (tangent, bitangent, normal)
+    }
 }
 
 #[enum_dispatch]
 pub trait Hittable {
+    /// Closest intersection within `ray_t`, including ones the material's opacity
+    /// would cut out
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord>;
+
+    /// Closest hit that isn't cut out by the material's opacity, the ray carries on
+    /// through masked out hits to whatever lies behind them
+    fn hit_opaque(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let mut min = ray_t.min;
+        while let Some(record) = self.hit(ray, Interval::from(min, ray_t.max)) {
+            if record.material.alpha_test(&record) {
+                return Some(record);
+            }
+            min = record.t;
+        }
+        None
+    }
+
+    // names and ids of the materials the object is made of, for id manifests
+    fn materials(&self) -> Vec<(String, u32)> {
+        Vec::new()
+    }
 }
 
 #[enum_dispatch(Hittable)]
@@ -50,3 +109,46 @@ pub enum Object {
     World,
     Sphere,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{Diffuse, Masked},
+        texture::{SolidColor, Texture},
+        utils::rng,
+        vec3::Color,
+    };
+
+    fn masked_sphere(alpha: f32) -> Sphere {
+        let diffuse = Material::Diffuse(Diffuse::new(&Color::new(0.5, 0.5, 0.5)));
+        let opacity = Texture::SolidColor(SolidColor::new(&Color::new(alpha, alpha, alpha)));
+        let masked = Material::Masked(Masked::new(diffuse, opacity));
+        Sphere::new(Point3::new(0., 0., -2.), 0.5, masked)
+    }
+
+    #[test]
+    fn cut_out_hits_are_skipped() {
+        let ray = Ray::new(Point3::new(0., 0., 0.), Vec3::new(0., 0., -1.));
+        let ray_t = || Interval::from(0.001, f32::INFINITY);
+
+        // the far side of the sphere is as cut out as the near side
+        let hole = masked_sphere(0.);
+        assert!(hole.hit(&ray, ray_t()).is_some());
+        assert!(hole.hit_opaque(&ray, ray_t()).is_none());
+
+        // half opaque, so a quarter of the rays make it through both sides
+        rng::seed(3);
+        let half = World::from(Object::Sphere(masked_sphere(0.5)));
+        let (mut near, mut through) = (0, 0);
+        for _ in 0..20_000 {
+            match half.hit(&ray, ray_t()) {
+                Some(record) if record.t < 2. => near += 1,
+                Some(_) => {}
+                None => through += 1,
+            }
+        }
+        assert!((near as f32 / 20_000. - 0.5).abs() < 0.02, "{near} near hits");
+        assert!((through as f32 / 20_000. - 0.25).abs() < 0.02, "{through} through");
+    }
+}
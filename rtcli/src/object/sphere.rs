@@ -1,7 +1,11 @@
+use core::f32::consts::PI;
+
 use crate::{
+    film::cryptomatte,
     material::Material,
+    stats::{self, Counter},
     utils::{self, Interval},
-    vec3::Point3,
+    vec3::{Point3, Vec3},
 };
 
 use super::{HitRecord, Hittable};
@@ -10,13 +14,18 @@ pub struct Sphere {
     center: Point3,
     radius: f32,
     material: Material,
+    material_name: String, // see `Material::name`, built once rather than per hit
+    material_id: u32,
 }
 
 impl Sphere {
     pub fn new(center: Point3, radius: f32, material: Material) -> Self {
+        let material_name = material.name();
         Self {
             center,
             radius,
+            material_id: cryptomatte::id(&material_name),
+            material_name,
             material,
         }
     }
@@ -24,6 +33,8 @@ impl Sphere {
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &crate::ray::Ray, ray_t: Interval) -> Option<HitRecord> {
+        stats::count(Counter::PrimitiveTests);
+
         // Eq for sphere is x^2 + y^2 + z^2 = r^2
         // To put the sphere in an arbitrary point (C) in space is :
         // (C_x - x)^2 + (C_y - y)^2 + (C_z - z)^2 = r^2
@@ -57,34 +68,50 @@ impl Hittable for Sphere {
 
         if let Some((minus, plus)) = utils::math::quadratic_formula(a, h, c) {
             // find the closest root to the camera that is within tmin and tmax
-            let root = match ray_t {
-                x if x.surrounds(minus) => minus,
-                x if x.surrounds(plus) => plus,
-                _ => return None,
-            };
+            for root in [minus, plus] {
+                if !ray_t.surrounds(root) {
+                    continue;
+                }
+
+                // assemble hit record
+                let point = ray.at(root);
+                let record = {
+                    let outward_normal = (point - self.center) / self.radius;
 
-            // assemble hit record
-            let point = ray.at(root);
-            let record = {
-                let outward_normal = (point - self.center) / self.radius;
-                let front_face = ray.direction().dot(&outward_normal) < 0.;
-                let normal = if front_face {
-                    outward_normal
-                } else {
-                    -outward_normal
+                    // spherical coordinates of the hit, theta is the angle up from -y and
+                    // phi the angle around the y axis from -x
+                    let theta = (-outward_normal.y()).acos();
+                    let phi = (-outward_normal.z()).atan2(outward_normal.x()) + PI;
+                    let dpdu = 2.
+                        * PI
+                        * self.radius
+                        * Vec3::new(phi.sin() * theta.sin(), 0., phi.cos() * theta.sin());
+                    let dpdv = PI
+                        * self.radius
+                        * Vec3::new(
+                            -phi.cos() * theta.cos(),
+                            theta.sin(),
+                            phi.sin() * theta.cos(),
+                        );
+
+                    let mut record =
+                        HitRecord::new(root, ray, point, outward_normal, self.material.clone());
+                    record.u = phi / (2. * PI);
+                    record.v = theta / PI;
+                    record.dpdu = dpdu;
+                    record.dpdv = dpdv;
+                    record.material_id = self.material_id;
+                    record
                 };
-                HitRecord {
-                    point,
-                    normal,
-                    t: root,
-                    front_face,
-                    material: self.material.clone(),
-                }
-            };
 
-            return Some(record);
+                return Some(record);
+            }
         }
 
         None
     }
+
+    fn materials(&self) -> Vec<(String, u32)> {
+        vec![(self.material_name.clone(), self.material_id)]
+    }
 }
@@ -1,13 +1,29 @@
 use std::sync::{Arc, RwLock};
 
-use crate::utils::Interval;
+use crate::{film::cryptomatte, utils::Interval};
 
 use super::{Hittable, Object};
 
+// an object with the name it's picked by in mattes
+struct Entry {
+    object: Object,
+    name: String,
+    id: u32, // cryptomatte id of the name
+}
+
+impl Entry {
+    fn new(name: String, object: Object) -> Self {
+        Self {
+            id: cryptomatte::id(&name),
+            name,
+            object,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct World {
-    objects: Arc<RwLock<Vec<Object>>>,
+    objects: Arc<RwLock<Vec<Entry>>>,
 }
 
 impl World {
@@ -17,32 +33,79 @@ impl World {
         }
     }
 
+    #[allow(dead_code)] // scenes are built up with push, only the tests start from one
     pub fn from(object: Object) -> Self {
-        Self { objects: Arc::from(RwLock::new(vec![object])) }
+        Self {
+            objects: Arc::from(RwLock::new(vec![Entry::new(
+                String::from("object0"),
+                object,
+            )])),
+        }
     }
 
+    #[allow(dead_code)] // no scene empties its world
     pub fn clear(&mut self) {
         self.objects.write().unwrap().clear()
     }
 
+    /// Adds an object named after its index, `object<index>`
     pub fn push(&mut self, object: Object) {
-        self.objects.write().unwrap().push(object)
+        let mut objects = self.objects.write().unwrap();
+        let name = format!("object{}", objects.len());
+        objects.push(Entry::new(name, object))
+    }
+
+    /// Adds an object with the name id passes and mattes know it by
+    pub fn push_named(&mut self, name: impl Into<String>, object: Object) {
+        self.objects
+            .write()
+            .unwrap()
+            .push(Entry::new(name.into(), object))
+    }
+
+    /// Names and ids of the top level objects
+    pub fn object_names(&self) -> Vec<(String, u32)> {
+        let objects = self.objects.read().unwrap();
+        objects
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.id))
+            .collect()
     }
 }
 
+// the hits of the objects are alpha tested as they're found, so a world's closest hit
+// is already opaque and a world nested in another isn't tested twice
 impl Hittable for World {
     fn hit(&self, ray: &crate::ray::Ray, ray_t: Interval) -> Option<crate::object::HitRecord> {
         let mut record = None;
 
         let mut closest = ray_t.max;
 
-        for object in self.objects.read().unwrap().iter() {
-            if let Some(rec) = object.hit(ray, Interval::from(ray_t.min, closest)) {
-                record = Some(rec.clone());
+        for entry in self.objects.read().unwrap().iter() {
+            let hit = entry.object.hit_opaque(ray, Interval::from(ray_t.min, closest));
+            if let Some(mut rec) = hit {
                 closest = rec.t;
+                rec.object_id = entry.id;
+                record = Some(rec);
             }
         }
 
         record
     }
-}
\ No newline at end of file
+
+    fn hit_opaque(
+        &self,
+        ray: &crate::ray::Ray,
+        ray_t: Interval,
+    ) -> Option<crate::object::HitRecord> {
+        self.hit(ray, ray_t)
+    }
+
+    fn materials(&self) -> Vec<(String, u32)> {
+        let objects = self.objects.read().unwrap();
+        objects
+            .iter()
+            .flat_map(|entry| entry.object.materials())
+            .collect()
+    }
+}
@@ -2,6 +2,3 @@
 
 pub type Result<T> = core::result::Result<T,Error>;
 
-// wrapper for the newtype pattern
-pub struct W<T>(pub T);
-
@@ -0,0 +1,188 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::stats::Stats;
+
+/// How far a render has got
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub done: u64,         // pixels with all their samples
+    pub total: u64,        // pixels in the image
+    pub stats: Stats,      // events counted so far
+    pub elapsed: Duration, // time since the render started
+}
+
+impl Progress {
+    /// Fraction of the pixels done, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.
+        } else {
+            self.done as f32 / self.total as f32
+        }
+    }
+
+    pub fn percentage(&self) -> f32 {
+        self.fraction() * 100.
+    }
+
+    /// Time left if the rest of the pixels take as long as the ones done, none until
+    /// some are done
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        (fraction > 0.).then(|| self.elapsed.mul_f32((1. - fraction) / fraction))
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            0. => 0.,
+            seconds => self.stats.rays() as f64 / seconds,
+        }
+    }
+}
+
+/// Told how a render is going. Called from the render's threads, so keep it quick
+pub trait ProgressObserver: Send + Sync {
+    /// After every tile of samples splatted onto the film
+    fn on_progress(&self, progress: &Progress);
+
+    /// Once the render has finished or been cancelled
+    fn on_finish(&self, _progress: &Progress) {}
+}
+
+/// Keeps quiet
+impl ProgressObserver for () {
+    fn on_progress(&self, _progress: &Progress) {}
+}
+
+/// Tells the observer if there is one
+impl<T: ProgressObserver> ProgressObserver for Option<T> {
+    fn on_progress(&self, progress: &Progress) {
+        if let Some(observer) = self {
+            observer.on_progress(progress)
+        }
+    }
+
+    fn on_finish(&self, progress: &Progress) {
+        if let Some(observer) = self {
+            observer.on_finish(progress)
+        }
+    }
+}
+
+/// Tells both observers, the first first
+impl<A: ProgressObserver, B: ProgressObserver> ProgressObserver for (A, B) {
+    fn on_progress(&self, progress: &Progress) {
+        self.0.on_progress(progress);
+        self.1.on_progress(progress);
+    }
+
+    fn on_finish(&self, progress: &Progress) {
+        self.0.on_finish(progress);
+        self.1.on_finish(progress);
+    }
+}
+
+/// Progress bar on stderr with the time left and the rays traced per second, followed
+/// by the time the render took
+#[derive(Debug)]
+pub struct ProgressBar {
+    width: usize,              // characters in the bar
+    last_draw: Mutex<Instant>, // the bar is redrawn a few times a second at most
+}
+
+impl ProgressBar {
+    const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn new() -> Self {
+        Self {
+            width: 40,
+            last_draw: Mutex::new(Instant::now() - Self::REDRAW_INTERVAL),
+        }
+    }
+
+    fn draw(&self, progress: &Progress) {
+        let filled = (progress.fraction() * self.width as f32) as usize;
+        let eta = progress
+            .eta()
+            .map_or_else(|| String::from("--:--"), format_duration);
+        let mut line = format!(
+            "\r[{}{}] {:6.2}%  eta {eta}",
+            "#".repeat(filled),
+            " ".repeat(self.width - filled),
+            progress.percentage(),
+        );
+        if progress.stats.rays() > 0 {
+            line += &format!("  {:.2} Mrays/s", progress.rays_per_second() / 1e6);
+        }
+
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(line.as_bytes());
+        let _ = stderr.flush();
+    }
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressObserver for ProgressBar {
+    fn on_progress(&self, progress: &Progress) {
+        // skips redraws while another thread is drawing
+        let Ok(mut last_draw) = self.last_draw.try_lock() else {
+            return;
+        };
+        if last_draw.elapsed() >= Self::REDRAW_INTERVAL {
+            self.draw(progress);
+            *last_draw = Instant::now();
+        }
+    }
+
+    fn on_finish(&self, progress: &Progress) {
+        self.draw(progress);
+        eprintln!();
+        eprint!("Rendered in {:.2}s", progress.elapsed.as_secs_f32());
+        if progress.stats.rays() > 0 {
+            eprint!(", {} rays", progress.stats.rays());
+        }
+        eprintln!();
+    }
+}
+
+// minutes and seconds, with hours once there are any
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds / 3600 {
+        0 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// Stops a render from another thread. Clones share the same flag, so a clone can be
+/// handed to the render and the original kept to cancel it with
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the render to stop, it finishes the tiles it's on and returns the film as
+    /// it is
+    pub fn cancel(&self) {
+        self.0.store(true, Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Relaxed)
+    }
+}
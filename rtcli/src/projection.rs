@@ -0,0 +1,440 @@
+use core::f32::consts::PI;
+use std::{
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use crate::{checkpoint::Persist, prelude::*, utils::math, vec3::Vec3};
+
+/// How points on the film map to rays leaving the camera
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Projection {
+    // through the viewport set up by the fov and focus distance, with depth of field
+    #[default]
+    Perspective,
+    // parallel rays through a view `height` world units tall
+    Orthographic { height: f32 },
+    // circle in the middle of the film covering `fov` degrees across
+    Fisheye { fov: f32, mapping: FisheyeMapping },
+    // whole sphere around the camera, longitude across and latitude down with the view
+    // direction in the middle
+    Equirectangular,
+    // six 90 degree faces in a 3 by 2 grid: right, left and up, then down, front and back
+    CubeMap,
+    // omni-directional stereo, an equirectangular image for each eye seen from eyes `ipd`
+    // world units apart, the left eye top or left
+    Stereo { ipd: f32, layout: StereoLayout },
+}
+
+/// How the images of the two eyes of a stereo panorama share the film
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoLayout {
+    TopBottom,  // left eye above the right
+    SideBySide, // left eye left of the right
+}
+
+/// How far from the centre of a fisheye image a ray at an angle from the view
+/// direction lands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    Equidistant, // distance proportional to the angle
+    Equisolid,   // equal areas of the image cover equal solid angles
+}
+
+impl Projection {
+    /// Width over height of the image the projection fills without stretching, none
+    /// when any shape will do
+    pub fn aspect_ratio(&self) -> Option<f32> {
+        match self {
+            Projection::Perspective | Projection::Orthographic { .. } => None,
+            Projection::Fisheye { .. } => Some(1.),
+            Projection::Equirectangular => Some(2.),
+            Projection::CubeMap => Some(1.5),
+            Projection::Stereo { layout, .. } => match layout {
+                StereoLayout::TopBottom => Some(1.),
+                StereoLayout::SideBySide => Some(4.),
+            },
+        }
+    }
+
+    /// Origin and direction in camera space, x right, y up and z forward, of the ray
+    /// through `(s, t)` on the film, both from 0 to 1 starting top left. None outside
+    /// the image circle of a fisheye, and for perspective rays, which go through the
+    /// camera's viewport
+    pub fn ray(&self, (s, t): (f32, f32), aspect_ratio: f32) -> Option<(Vec3, Vec3)> {
+        let forward = Vec3::new(0., 0., 1.);
+        match *self {
+            Projection::Perspective => None,
+            Projection::Orthographic { height } => {
+                let origin = Vec3::new((s - 0.5) * height * aspect_ratio, (0.5 - t) * height, 0.);
+                Some((origin, forward))
+            }
+            Projection::Fisheye { fov, mapping } => {
+                // the circle fits the shorter side of the film
+                let x = (2. * s - 1.) * aspect_ratio.max(1.);
+                let y = (1. - 2. * t) * (1. / aspect_ratio).max(1.);
+                let r = x.hypot(y);
+                if r > 1. {
+                    return None;
+                }
+
+                let max_theta = math::deg_to_rad(fov) / 2.;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * max_theta,
+                    FisheyeMapping::Equisolid => 2. * (r * (max_theta / 2.).sin()).asin(),
+                };
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                let direction = if r > 0. {
+                    Vec3::new(sin_theta * x / r, sin_theta * y / r, cos_theta)
+                } else {
+                    forward
+                };
+                Some((Vec3::new(0., 0., 0.), direction))
+            }
+            Projection::Equirectangular => {
+                let longitude = (s - 0.5) * 2. * PI;
+                let latitude = (0.5 - t) * PI;
+                Some((Vec3::new(0., 0., 0.), spherical(longitude, latitude)))
+            }
+            Projection::CubeMap => {
+                let column = ((s * 3.) as usize).min(2);
+                let row = ((t * 2.) as usize).min(1);
+                // position on the face from -1 to 1, right and up
+                let a = (s * 3. - column as f32) * 2. - 1.;
+                let b = 1. - (t * 2. - row as f32) * 2.;
+
+                let x = Vec3::new(1., 0., 0.);
+                let y = Vec3::new(0., 1., 0.);
+                let z = forward;
+                // view direction, right and up of each face
+                let (face, right, up) = match (row, column) {
+                    (0, 0) => (x, -z, y),
+                    (0, 1) => (-x, z, y),
+                    (0, _) => (y, x, -z),
+                    (_, 0) => (-y, x, z),
+                    (_, 1) => (z, x, y),
+                    (_, _) => (-z, -x, y),
+                };
+                Some((Vec3::new(0., 0., 0.), face + a * right + b * up))
+            }
+            Projection::Stereo { ipd, layout } => {
+                // the half of the film of each eye is an equirectangular image
+                let (left_eye, s, t) = match layout {
+                    StereoLayout::TopBottom => (t < 0.5, s, (t * 2.).fract()),
+                    StereoLayout::SideBySide => (s < 0.5, (s * 2.).fract(), t),
+                };
+                let longitude = (s - 0.5) * 2. * PI;
+                let latitude = (0.5 - t) * PI;
+
+                // each eye sits on a circle ipd across, to the side of the direction it
+                // looks in
+                let (sin_longitude, cos_longitude) = longitude.sin_cos();
+                let right = Vec3::new(cos_longitude, 0., -sin_longitude);
+                let eye = if left_eye { -ipd / 2. } else { ipd / 2. };
+                Some((eye * right, spherical(longitude, latitude)))
+            }
+        }
+    }
+}
+
+// unit direction at a longitude from the z axis towards x and a latitude up from the
+// horizon, in radians
+pub(crate) fn spherical(longitude: f32, latitude: f32) -> Vec3 {
+    let (sin_longitude, cos_longitude) = longitude.sin_cos();
+    let (sin_latitude, cos_latitude) = latitude.sin_cos();
+    Vec3::new(
+        cos_latitude * sin_longitude,
+        sin_latitude,
+        cos_latitude * cos_longitude,
+    )
+}
+
+// a tag for the variant followed by its parameters
+impl Persist for Projection {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        match *self {
+            Projection::Perspective => 0u32.write(out),
+            Projection::Orthographic { height } => {
+                1u32.write(out)?;
+                height.write(out)
+            }
+            Projection::Fisheye { fov, mapping } => {
+                2u32.write(out)?;
+                fov.write(out)?;
+                (mapping == FisheyeMapping::Equisolid).write(out)
+            }
+            Projection::Equirectangular => 3u32.write(out),
+            Projection::CubeMap => 4u32.write(out),
+            Projection::Stereo { ipd, layout } => {
+                5u32.write(out)?;
+                ipd.write(out)?;
+                (layout == StereoLayout::SideBySide).write(out)
+            }
+        }
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(match u32::read(input)? {
+            0 => Projection::Perspective,
+            1 => Projection::Orthographic {
+                height: f32::read(input)?,
+            },
+            2 => Projection::Fisheye {
+                fov: f32::read(input)?,
+                mapping: match bool::read(input)? {
+                    true => FisheyeMapping::Equisolid,
+                    false => FisheyeMapping::Equidistant,
+                },
+            },
+            3 => Projection::Equirectangular,
+            4 => Projection::CubeMap,
+            5 => Projection::Stereo {
+                ipd: f32::read(input)?,
+                layout: match bool::read(input)? {
+                    true => StereoLayout::SideBySide,
+                    false => StereoLayout::TopBottom,
+                },
+            },
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown projection {tag}"),
+                ))
+            }
+        })
+    }
+}
+
+impl FromStr for Projection {
+    type Err = Error;
+
+    /// Parses `perspective`, `orthographic` with an optional `=height`, `fisheye` or
+    /// `fisheye-equisolid` with an optional `=fov` in degrees, `equirectangular`,
+    /// `cubemap`, or `stereo-top-bottom` or `stereo-side-by-side` with an optional
+    /// `=ipd`, 0.064 by default for scenes in metres
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, parameter) = match s.split_once('=') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (s, None),
+        };
+        let parse = |parameter: Option<&str>, default: f32| match parameter {
+            Some(value) => value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| *value > 0.)
+                .ok_or_else(|| Error::Generic(format!("invalid {name} parameter {value}"))),
+            None => Ok(default),
+        };
+        let fisheye = |mapping| -> Result<Self> {
+            let fov = parse(parameter, 180.)?;
+            if fov > 360. {
+                return Err(Error::Generic(format!("fisheye fov {fov} is over 360")));
+            }
+            Ok(Projection::Fisheye { fov, mapping })
+        };
+
+        match (name.to_ascii_lowercase().as_str(), parameter) {
+            ("perspective", None) => Ok(Projection::Perspective),
+            ("orthographic", height) => Ok(Projection::Orthographic {
+                height: parse(height, 2.)?,
+            }),
+            ("fisheye", _) => fisheye(FisheyeMapping::Equidistant),
+            ("fisheye-equisolid", _) => fisheye(FisheyeMapping::Equisolid),
+            ("equirectangular", None) => Ok(Projection::Equirectangular),
+            ("cubemap", None) => Ok(Projection::CubeMap),
+            ("stereo-top-bottom", ipd) => Ok(Projection::Stereo {
+                ipd: parse(ipd, 0.064)?,
+                layout: StereoLayout::TopBottom,
+            }),
+            ("stereo-side-by-side", ipd) => Ok(Projection::Stereo {
+                ipd: parse(ipd, 0.064)?,
+                layout: StereoLayout::SideBySide,
+            }),
+            _ => Err(Error::Generic(format!(
+                "unknown projection {s}, expected perspective, orthographic[=height], fisheye[=fov], fisheye-equisolid[=fov], equirectangular, cubemap, stereo-top-bottom[=ipd] or stereo-side-by-side[=ipd]"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_direction(direction: Vec3, expected: Vec3) {
+        let (direction, expected) = (direction.unit(), expected.unit());
+        assert!(
+            (direction - expected).len() < 1e-4,
+            "{direction:?} isn't {expected:?}"
+        );
+    }
+
+    // angle in degrees between a direction and the view direction
+    fn off_axis(direction: Vec3) -> f32 {
+        direction.unit().z().clamp(-1., 1.).acos().to_degrees()
+    }
+
+    #[test]
+    fn perspective_rays_come_from_the_viewport() {
+        assert!(Projection::Perspective.ray((0.5, 0.5), 1.5).is_none());
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let projection = Projection::Orthographic { height: 2. };
+        let (origin, direction) = projection.ray((0.5, 0.5), 2.).unwrap();
+        assert_direction(direction, Vec3::new(0., 0., 1.));
+        assert!(origin.len() < 1e-6);
+
+        let (origin, direction) = projection.ray((0., 0.), 2.).unwrap();
+        assert_direction(direction, Vec3::new(0., 0., 1.));
+        assert!((origin - Vec3::new(-2., 1., 0.)).len() < 1e-6);
+    }
+
+    #[test]
+    fn fisheye_angles_grow_to_the_edge_of_the_circle() {
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let projection = Projection::Fisheye { fov: 180., mapping };
+            let (_, centre) = projection.ray((0.5, 0.5), 1.).unwrap();
+            assert_direction(centre, Vec3::new(0., 0., 1.));
+
+            // the circle's edge is at half the fov, right is right and up is up
+            let (_, right) = projection.ray((1., 0.5), 1.).unwrap();
+            assert_direction(right, Vec3::new(1., 0., 0.));
+            let (_, up) = projection.ray((0.5, 0.), 1.).unwrap();
+            assert_direction(up, Vec3::new(0., 1., 0.));
+
+            // nothing in the corners, and on a wide film the circle fits its height
+            assert!(projection.ray((0.05, 0.05), 1.).is_none());
+            assert!(projection.ray((0.8, 0.5), 2.).is_none());
+            let (_, edge) = projection.ray((0.75, 0.5), 2.).unwrap();
+            assert!((off_axis(edge) - 90.).abs() < 1e-2);
+        }
+
+        // halfway out is half the angle when equidistant, less when equisolid
+        let halfway = |mapping| {
+            let projection = Projection::Fisheye { fov: 180., mapping };
+            off_axis(projection.ray((0.75, 0.5), 1.).unwrap().1)
+        };
+        assert!((halfway(FisheyeMapping::Equidistant) - 45.).abs() < 1e-2);
+        let equisolid = 2. * (0.5 * 45f32.to_radians().sin()).asin().to_degrees();
+        assert!((halfway(FisheyeMapping::Equisolid) - equisolid).abs() < 1e-2);
+    }
+
+    #[test]
+    fn equirectangular_rays_cover_the_sphere() {
+        let projection = Projection::Equirectangular;
+        let direction = |s, t| projection.ray((s, t), 2.).unwrap().1;
+        assert_direction(direction(0.5, 0.5), Vec3::new(0., 0., 1.));
+        assert_direction(direction(0.75, 0.5), Vec3::new(1., 0., 0.));
+        assert_direction(direction(0.25, 0.5), Vec3::new(-1., 0., 0.));
+        assert_direction(direction(0., 0.5), Vec3::new(0., 0., -1.));
+        assert_direction(direction(0.5, 0.), Vec3::new(0., 1., 0.));
+        assert_direction(direction(0.3, 1.), Vec3::new(0., -1., 0.));
+    }
+
+    #[test]
+    fn cube_map_faces_meet_at_their_edges() {
+        let projection = Projection::CubeMap;
+        let direction = |s, t| projection.ray((s, t), 1.5).unwrap().1;
+
+        // the middle of each face of the 3 by 2 grid
+        let faces = [
+            ((1. / 6., 0.25), Vec3::new(1., 0., 0.)),
+            ((0.5, 0.25), Vec3::new(-1., 0., 0.)),
+            ((5. / 6., 0.25), Vec3::new(0., 1., 0.)),
+            ((1. / 6., 0.75), Vec3::new(0., -1., 0.)),
+            ((0.5, 0.75), Vec3::new(0., 0., 1.)),
+            ((5. / 6., 0.75), Vec3::new(0., 0., -1.)),
+        ];
+        for ((s, t), expected) in faces {
+            assert_direction(direction(s, t), expected);
+        }
+
+        // the right edge of the front face is the left edge of the right face
+        let front_right = direction(2. / 3. - 1e-6, 0.6);
+        let right_left = direction(1e-6, 0.1);
+        assert_direction(front_right, right_left);
+        // the top edge of the front face is the bottom edge of the up face
+        let front_top = direction(0.6, 0.5 + 1e-6);
+        let up_bottom = direction(2.8 / 3., 0.5 - 1e-6);
+        assert_direction(front_top, up_bottom);
+    }
+
+    #[test]
+    fn parses_projections_and_their_parameters() {
+        let parse = |s: &str| s.parse::<Projection>();
+        assert_eq!(
+            parse("orthographic=3").unwrap(),
+            Projection::Orthographic { height: 3. }
+        );
+        assert_eq!(
+            parse("fisheye-equisolid").unwrap(),
+            Projection::Fisheye {
+                fov: 180.,
+                mapping: FisheyeMapping::Equisolid
+            }
+        );
+        assert_eq!(parse("CubeMap").unwrap(), Projection::CubeMap);
+        assert!(parse("fisheye=400").is_err());
+        assert!(parse("orthographic=0").is_err());
+        assert!(parse("equirectangular=1").is_err());
+        assert!(parse("panini").is_err());
+    }
+
+    #[test]
+    fn stereo_eyes_are_ipd_apart_looking_the_same_way() {
+        let ipd = 0.064;
+        let equirectangular = |s, t| Projection::Equirectangular.ray((s, t), 2.).unwrap().1;
+        for layout in [StereoLayout::TopBottom, StereoLayout::SideBySide] {
+            let projection = Projection::Stereo { ipd, layout };
+            // the same point of the panorama on the film of each eye
+            let eyes = |s: f32, t: f32| match layout {
+                StereoLayout::TopBottom => ((s, t / 2.), (s, 0.5 + t / 2.)),
+                StereoLayout::SideBySide => ((s / 2., t), (0.5 + s / 2., t)),
+            };
+
+            for (s, t) in [(0.5, 0.5), (0.1, 0.3), (0.8, 0.6), (0.35, 0.9)] {
+                let (left, right) = eyes(s, t);
+                let (left_origin, left_direction) = projection.ray(left, 1.).unwrap();
+                let (right_origin, right_direction) = projection.ray(right, 1.).unwrap();
+
+                // each eye sees the panorama, from the side of its view direction
+                assert_direction(left_direction, equirectangular(s, t));
+                assert_direction(right_direction, equirectangular(s, t));
+                assert!(((left_origin - right_origin).len() - ipd).abs() < 1e-6);
+                assert!(left_origin.dot(&left_direction).abs() < 1e-6);
+                assert!(left_origin.y() == 0. && right_origin.y() == 0.);
+            }
+
+            // looking forward the left eye is on the left
+            let (left, right) = eyes(0.5, 0.5);
+            assert!((projection.ray(left, 1.).unwrap().0.x() + ipd / 2.).abs() < 1e-6);
+            assert!((projection.ray(right, 1.).unwrap().0.x() - ipd / 2.).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn stereo_layouts_stack_two_panoramas() {
+        let top_bottom = "stereo-top-bottom".parse::<Projection>().unwrap();
+        assert_eq!(
+            top_bottom,
+            Projection::Stereo {
+                ipd: 0.064,
+                layout: StereoLayout::TopBottom
+            }
+        );
+        assert_eq!(top_bottom.aspect_ratio(), Some(1.));
+
+        let side_by_side = "stereo-side-by-side=0.1".parse::<Projection>().unwrap();
+        assert_eq!(
+            side_by_side,
+            Projection::Stereo {
+                ipd: 0.1,
+                layout: StereoLayout::SideBySide
+            }
+        );
+        assert_eq!(side_by_side.aspect_ratio(), Some(4.));
+        assert!("stereo-top-bottom=-1".parse::<Projection>().is_err());
+    }
+}
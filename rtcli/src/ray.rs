@@ -4,6 +4,7 @@ use crate::vec3::{Point3, Vec3};
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    wavelength: Option<f32>, // wavelength in nm the ray carries, set once a path hits a dispersive material
 }
 
 impl Ray { 
@@ -11,9 +12,15 @@ impl Ray {
         Self {
             origin,
             direction,
+            wavelength: None,
         }
     }
 
+    pub fn with_wavelength(mut self, wavelength: Option<f32>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
     pub fn origin(&self) -> &Point3 {
         &self.origin
     }
@@ -22,6 +29,10 @@ impl Ray {
         &self.direction
     }
 
+    pub fn wavelength(&self) -> Option<f32> {
+        self.wavelength
+    }
+
     pub fn at(&self, t: f32) -> Point3 {
         self.origin + self.direction * t
     }
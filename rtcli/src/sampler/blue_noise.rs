@@ -0,0 +1,86 @@
+use std::sync::OnceLock;
+
+use super::{
+    hash,
+    sobol::{sample_1d, sample_2d},
+    to_unit_float, Sample,
+};
+
+// side of the tiled blue noise mask in pixels
+const SIZE: u32 = 64;
+
+/// The same scrambled sobol sequence in every pixel, shifted by a blue noise mask so
+/// neighbouring pixels get very different offsets and the remaining error looks like
+/// fine grained blue noise. Georgiev & Fajardo - "Blue-noise Dithered Sampling" (2016)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlueNoise;
+
+impl Sample for BlueNoise {
+    fn get_1d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> f32 {
+        let value = sample_1d(index, hash(&[dimension])) + shift(pixel, dimension);
+        value.fract().min(1. - f32::EPSILON)
+    }
+
+    fn get_2d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> (f32, f32) {
+        let (u, v) = sample_2d(index, hash(&[dimension]));
+        (
+            (u + shift(pixel, dimension)).fract().min(1. - f32::EPSILON),
+            (v + shift(pixel, dimension + 1))
+                .fract()
+                .min(1. - f32::EPSILON),
+        )
+    }
+}
+
+// value of the mask at the pixel, offset by a different amount for every dimension so
+// dimensions don't share the same shifts
+fn shift(pixel: (u32, u32), dimension: u32) -> f32 {
+    let offset = hash(&[dimension, 0xb10e]);
+    let x = (pixel.0 % SIZE + offset % SIZE) % SIZE;
+    let y = (pixel.1 % SIZE + (offset >> 16) % SIZE) % SIZE;
+    mask()[(y * SIZE + x) as usize]
+}
+
+fn mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(generate_mask)
+}
+
+// ranks the pixels by repeatedly filling the largest void, the centre of the area with
+// the lowest gaussian weighted density of already ranked pixels, so every threshold of
+// the mask gives evenly spread pixels. Ulichney - "The void-and-cluster method" (1993)
+fn generate_mask() -> Vec<f32> {
+    const SIGMA: f32 = 1.5;
+    let len = (SIZE * SIZE) as usize;
+
+    // falloff for each toroidally wrapped offset
+    let distance = |d: u32| d.min(SIZE - d) as f32;
+    let falloff: Vec<f32> = (0..SIZE * SIZE)
+        .map(|i| {
+            let (dx, dy) = (distance(i % SIZE), distance(i / SIZE));
+            (-(dx * dx + dy * dy) / (2. * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    // a little deterministic noise breaks the ties between equally empty pixels
+    let mut energy: Vec<f32> = (0..len as u32)
+        .map(|i| to_unit_float(hash(&[i])) * 1e-3)
+        .collect();
+    let mut mask = vec![f32::NAN; len];
+
+    for rank in 0..len {
+        let void = (0..len)
+            .filter(|&i| mask[i].is_nan())
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap();
+        mask[void] = (rank as f32 + 0.5) / len as f32;
+
+        let (vx, vy) = (void as u32 % SIZE, void as u32 / SIZE);
+        for (i, energy) in energy.iter_mut().enumerate() {
+            let dx = (i as u32 % SIZE + SIZE - vx) % SIZE;
+            let dy = (i as u32 / SIZE + SIZE - vy) % SIZE;
+            *energy += falloff[(dy * SIZE + dx) as usize];
+        }
+    }
+    mask
+}
@@ -0,0 +1,46 @@
+use super::{hash, permutation_element, to_unit_float, Sample};
+
+// bases of the dimensions, later dimensions fall back to random numbers as the
+// sequences of large primes are badly correlated
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton sequence over the samples of a pixel, with the digits owen scrambled by a
+/// seed per pixel and dimension so the large bases don't line up and pixels are
+/// decorrelated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Halton;
+
+impl Sample for Halton {
+    fn get_1d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> f32 {
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                let seed = hash(&[pixel.0, pixel.1, dimension]);
+                scrambled_radical_inverse(base, index, seed)
+            }
+            None => to_unit_float(hash(&[pixel.0, pixel.1, dimension, index])),
+        }
+    }
+}
+
+/// Mirrors the digits of `index` in `base` around the decimal point, permuting each
+/// digit depending on the digits before it
+fn scrambled_radical_inverse(base: u32, mut index: u32, seed: u32) -> f32 {
+    let inverse_base = 1. / base as f64;
+    let mut reversed = 0u64;
+    let mut scale = 1.;
+    let mut digits = 0;
+    // keep going past the last digit of the index as scrambled zeros aren't zero
+    while scale > f32::EPSILON as f64 / 2. {
+        let next = index / base;
+        let digit = index - next * base;
+        let digit = permutation_element(digit, base, hash(&[seed, digits, reversed as u32]));
+        reversed = reversed * base as u64 + digit as u64;
+        scale *= inverse_base;
+        index = next;
+        digits += 1;
+    }
+    ((reversed as f64 * scale) as f32).min(1. - f32::EPSILON)
+}
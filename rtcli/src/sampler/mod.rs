@@ -0,0 +1,276 @@
+use std::{
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use enum_dispatch::enum_dispatch;
+
+pub mod blue_noise;
+pub mod halton;
+pub mod sobol;
+pub mod stratified;
+pub use blue_noise::BlueNoise;
+pub use halton::Halton;
+pub use sobol::Sobol;
+pub use stratified::Stratified;
+
+use crate::{checkpoint::Persist, prelude::*};
+
+#[enum_dispatch(Sample)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampler {
+    Independent, // uniform hashed numbers
+    Stratified,  // jittered within a grid of strata
+    Halton,      // radical inverses in prime bases
+    Sobol,       // owen scrambled sobol sequence
+    BlueNoise,   // sobol sequence shifted per pixel by a blue noise mask
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler::Independent(Independent)
+    }
+}
+
+impl Sampler {
+    /// The sampler laid out for pixels taking `samples` samples each, which only the
+    /// stratified sampler needs to know
+    pub fn for_samples(self, samples: u32) -> Self {
+        match self {
+            Sampler::Stratified(_) => Stratified::new(samples).into(),
+            sampler => sampler,
+        }
+    }
+}
+
+// the kind of sampler, the stratified one is laid out for its samples again with
+// `for_samples`
+impl Persist for Sampler {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let tag: u32 = match self {
+            Sampler::Independent(_) => 0,
+            Sampler::Stratified(_) => 1,
+            Sampler::Halton(_) => 2,
+            Sampler::Sobol(_) => 3,
+            Sampler::BlueNoise(_) => 4,
+        };
+        tag.write(out)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(match u32::read(input)? {
+            0 => Independent.into(),
+            1 => Stratified::new(1).into(),
+            2 => Halton.into(),
+            3 => Sobol.into(),
+            4 => BlueNoise.into(),
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown sampler {tag}"),
+                ))
+            }
+        })
+    }
+}
+
+impl FromStr for Sampler {
+    type Err = Error;
+
+    /// Parses `independent`, `stratified`, `halton`, `sobol` or `blue-noise`. The
+    /// stratified sampler is laid out for a single sample until `for_samples` is given
+    /// the pixels' samples
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "independent" => Ok(Independent.into()),
+            "stratified" => Ok(Stratified::new(1).into()),
+            "halton" => Ok(Halton.into()),
+            "sobol" => Ok(Sobol.into()),
+            "blue-noise" => Ok(BlueNoise.into()),
+            _ => Err(Error::Generic(format!(
+                "unknown sampler {s}, expected independent, stratified, halton, sobol or blue-noise"
+            ))),
+        }
+    }
+}
+
+#[enum_dispatch]
+pub trait Sample {
+    /// Value in [0, 1) for `dimension` of the `index`th sample taken of `pixel`
+    fn get_1d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> f32;
+
+    /// Pair of values that are well distributed together, taking up `dimension` and
+    /// the one after it
+    fn get_2d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> (f32, f32) {
+        (
+            self.get_1d(pixel, index, dimension),
+            self.get_1d(pixel, index, dimension + 1),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Independent;
+
+// hashed rather than drawn from a generator, so a sample is the same whichever thread
+// takes it and renders can be resumed
+impl Sample for Independent {
+    fn get_1d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> f32 {
+        to_unit_float(hash(&[pixel.0, pixel.1, index, dimension]))
+    }
+}
+
+/// Mixes the values into 32 well distributed bits, used to decorrelate pixels and
+/// dimensions from each other
+pub fn hash(values: &[u32]) -> u32 {
+    // splitmix64 finaliser over each value
+    let mix = |mut x: u64| {
+        x ^= x >> 30;
+        x = x.wrapping_mul(0xbf58476d1ce4e5b9);
+        x ^= x >> 27;
+        x = x.wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    };
+    let hash = values.iter().fold(0x9e3779b97f4a7c15, |hash, &value| {
+        mix(hash ^ value as u64).wrapping_add(0x9e3779b97f4a7c15)
+    });
+    (hash >> 32) as u32
+}
+
+/// Maps 32 random bits to a float in [0, 1)
+pub fn to_unit_float(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Element `i` of a random permutation of 0..`len` chosen by `seed`, without
+/// building the permutation. Kensler - "Correlated Multi-Jittered Sampling" (2013)
+pub fn permutation_element(mut i: u32, len: u32, seed: u32) -> u32 {
+    // mask covering len - 1, values past len are cycled through the permutation again
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXEL: (u32, u32) = (3, 7);
+
+    fn samplers(samples: u32) -> [Sampler; 5] {
+        ["independent", "stratified", "halton", "sobol", "blue-noise"]
+            .map(|name| name.parse::<Sampler>().unwrap().for_samples(samples))
+    }
+
+    // how many of the points land in each cell of a `nx` by `ny` grid
+    fn occupancy(points: impl Iterator<Item = (f32, f32)>, nx: u32, ny: u32) -> Vec<u32> {
+        let mut cells = vec![0; (nx * ny) as usize];
+        for (x, y) in points {
+            let (i, j) = ((x * nx as f32) as u32, (y * ny as f32) as u32);
+            cells[(j * nx + i) as usize] += 1;
+        }
+        cells
+    }
+
+    #[test]
+    fn samples_are_in_the_unit_interval() {
+        for sampler in samplers(16) {
+            for index in 0..64 {
+                for dimension in 0..40 {
+                    let x = sampler.get_1d(PIXEL, index, dimension);
+                    let (y, z) = sampler.get_2d(PIXEL, index, dimension);
+                    for value in [x, y, z] {
+                        assert!((0. ..1.).contains(&value), "{sampler:?} gave {value}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_samples_fill_every_stratum() {
+        let sampler = Stratified::new(16);
+        for dimension in [0, 1, 5] {
+            // every round of 16 samples covers the strata again
+            for round in 0..2 {
+                let indices = round * 16..(round + 1) * 16;
+                let lines = indices
+                    .clone()
+                    .map(|index| (sampler.get_1d(PIXEL, index, dimension), 0.));
+                assert!(occupancy(lines, 16, 1).iter().all(|&n| n == 1));
+                let cells = indices.map(|index| sampler.get_2d(PIXEL, index, dimension));
+                assert!(occupancy(cells, 4, 4).iter().all(|&n| n == 1));
+            }
+        }
+
+        // counts without a square grid use one cell each of the smallest that fits
+        let sampler = Stratified::new(8);
+        let cells = (0..8).map(|index| sampler.get_2d(PIXEL, index, 0));
+        assert!(occupancy(cells, 3, 3).iter().all(|&n| n <= 1));
+    }
+
+    #[test]
+    fn sobol_points_are_a_net() {
+        // every power of two prefix is stratified in both dimensions and in the grid
+        for dimension in [0, 2] {
+            let points: Vec<_> = (0..16)
+                .map(|index| Sobol.get_2d(PIXEL, index, dimension))
+                .collect();
+            for (nx, ny) in [(16, 1), (1, 16), (4, 4), (2, 8), (8, 2)] {
+                let cells = occupancy(points.iter().copied(), nx, ny);
+                assert!(cells.iter().all(|&n| n == 1), "{nx}x{ny} cells {cells:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn halton_samples_are_stratified_in_their_base() {
+        for (dimension, base) in [(0, 2), (1, 3), (2, 5)] {
+            let count = base * base;
+            let lines = (0..count).map(|index| (Halton.get_1d(PIXEL, index, dimension), 0.));
+            assert!(occupancy(lines, count, 1).iter().all(|&n| n == 1));
+        }
+    }
+
+    #[test]
+    fn permutation_elements_form_a_permutation() {
+        for len in [1, 2, 3, 7, 16, 100] {
+            for seed in [0, 1, 0xdead_beef] {
+                let mut seen = vec![false; len as usize];
+                for i in 0..len {
+                    let element = permutation_element(i, len, seed) as usize;
+                    assert!(!seen[element], "{element} twice in a permutation of {len}");
+                    seen[element] = true;
+                }
+            }
+        }
+    }
+}
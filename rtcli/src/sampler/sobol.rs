@@ -0,0 +1,62 @@
+use super::{hash, to_unit_float, Sample};
+
+/// First two dimensions of the sobol sequence with nested uniform (owen) scrambling,
+/// padded to any dimension by shuffling the sample order with a different seed per
+/// dimension. Burley - "Practical Hash-based Owen Scrambling" (2020)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sobol;
+
+impl Sample for Sobol {
+    fn get_1d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> f32 {
+        sample_1d(index, hash(&[pixel.0, pixel.1, dimension]))
+    }
+
+    fn get_2d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> (f32, f32) {
+        sample_2d(index, hash(&[pixel.0, pixel.1, dimension]))
+    }
+}
+
+/// `index`th point of the 1d sequence scrambled by `seed`
+pub fn sample_1d(index: u32, seed: u32) -> f32 {
+    let index = nested_uniform_scramble(index, seed);
+    to_unit_float(nested_uniform_scramble(sobol_0(index), hash(&[seed, 0])))
+}
+
+/// `index`th point of the 2d sequence scrambled by `seed`
+pub fn sample_2d(index: u32, seed: u32) -> (f32, f32) {
+    let index = nested_uniform_scramble(index, seed);
+    (
+        to_unit_float(nested_uniform_scramble(sobol_0(index), hash(&[seed, 0]))),
+        to_unit_float(nested_uniform_scramble(sobol_1(index), hash(&[seed, 1]))),
+    )
+}
+
+// the first dimension is the van der corput sequence
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+// the second dimension's direction numbers follow from its primitive polynomial x + 1
+fn sobol_1(index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut value = 0;
+    for bit in 0..32 {
+        if (index >> bit) & 1 == 1 {
+            value ^= direction;
+        }
+        direction ^= direction >> 1;
+    }
+    value
+}
+
+// flips each bit depending on the bits above it, randomly permuting the points within
+// every elementary interval
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x ^= x.wrapping_mul(0x3d20adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x05526c56);
+    x ^= x.wrapping_mul(0x53a22864);
+    x.reverse_bits()
+}
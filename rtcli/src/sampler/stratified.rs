@@ -0,0 +1,46 @@
+use super::{hash, permutation_element, to_unit_float, Sample};
+
+/// Jitters each sample inside its own cell of a grid over the pixel, each dimension
+/// visits the cells in a different random order. Samples past `samples_per_pixel`
+/// start over with a new order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stratified {
+    samples_per_pixel: u32,
+}
+
+impl Stratified {
+    pub fn new(samples_per_pixel: u32) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+        }
+    }
+
+    // cell of the sample out of `cells`, each cell is used once per round of samples
+    fn cell(&self, pixel: (u32, u32), index: u32, dimension: u32, cells: u32) -> u32 {
+        let round = index / self.samples_per_pixel;
+        let seed = hash(&[pixel.0, pixel.1, dimension, round]);
+        permutation_element(index % self.samples_per_pixel, cells, seed)
+    }
+}
+
+impl Sample for Stratified {
+    fn get_1d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> f32 {
+        let n = self.samples_per_pixel;
+        let cell = self.cell(pixel, index, dimension, n);
+        let jitter = to_unit_float(hash(&[pixel.0, pixel.1, dimension, index]));
+        ((cell as f32 + jitter) / n as f32).min(1. - f32::EPSILON)
+    }
+
+    fn get_2d(&self, pixel: (u32, u32), index: u32, dimension: u32) -> (f32, f32) {
+        // smallest grid with at least a cell per sample
+        let nx = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let ny = self.samples_per_pixel.div_ceil(nx);
+        let cell = self.cell(pixel, index, dimension, nx * ny);
+
+        let jitter = |dimension: u32| to_unit_float(hash(&[pixel.0, pixel.1, dimension, index]));
+        (
+            ((cell % nx) as f32 + jitter(dimension)) / nx as f32,
+            ((cell / nx) as f32 + jitter(dimension + 1)) / ny as f32,
+        )
+    }
+}
@@ -1,12 +1,10 @@
 use crate::camera::Camera;
-use crate::material::{self, Dielectric, Diffuse, Material, Metallic};
-use crate::object::{self, Object, Sphere, World};
+use crate::material::{self, Material};
+use crate::object::{self, Object, World};
 use crate::utils::rng::{random_float, random_float_range};
 use crate::vec3::{Color, Point3, Vec3};
-use image::ImageBuffer;
-use rand::{thread_rng, Rng};
 
-pub fn large_scene() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
+pub fn large_scene() -> (Camera, World) {
     let aspect_ratio = 16. / 9.;
     let image_width: u32 = 1920;
     let fov = 25.;
@@ -24,14 +22,16 @@ pub fn large_scene() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
 
     // floor
     let floor_material = Material::Diffuse(material::Diffuse::new(&Color::new(0.5, 0.5, 0.5)));
-    world.push(Object::Sphere(object::Sphere::new(
-        Vec3::new(0., -1000., 0.),
-        1000.,
-        floor_material,
-    )));
+    world.push_named(
+        "floor",
+        Object::Sphere(object::Sphere::new(
+            Vec3::new(0., -1000., 0.),
+            1000.,
+            floor_material,
+        )),
+    );
 
     // generate random spheres
-    let mut rng = thread_rng();
     for x in -15..15 {
         for y in -15..15 {
             let centre = Point3::new(
@@ -40,7 +40,7 @@ pub fn large_scene() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
                 y as f32 + 0.9 * random_float(),
             );
             let random_material = {
-                match rng.gen_range(0..100) {
+                match (random_float() * 100.) as u32 {
                     0..=79 => {
                         // diffuse material
                         let albedo = Color::random() * Color::random();
@@ -71,25 +71,34 @@ pub fn large_scene() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
         Material::Metallic(material::Metallic::new(&Color::new(0.7, 0.6, 0.5), 0.));
 
     let big_spheres = vec![
-        Object::Sphere(object::Sphere::new(
-            Vec3::new(0., 1., 0.),
-            1.0,
-            glass_material,
-        )),
-        Object::Sphere(object::Sphere::new(
-            Vec3::new(4., 1., 0.),
-            1.0,
-            metal_material,
-        )),
-        Object::Sphere(object::Sphere::new(
-            Vec3::new(-4., 1., 0.),
-            1.0,
-            diffuse_material,
-        )),
+        (
+            "glass_sphere",
+            Object::Sphere(object::Sphere::new(
+                Vec3::new(0., 1., 0.),
+                1.0,
+                glass_material,
+            )),
+        ),
+        (
+            "metal_sphere",
+            Object::Sphere(object::Sphere::new(
+                Vec3::new(4., 1., 0.),
+                1.0,
+                metal_material,
+            )),
+        ),
+        (
+            "diffuse_sphere",
+            Object::Sphere(object::Sphere::new(
+                Vec3::new(-4., 1., 0.),
+                1.0,
+                diffuse_material,
+            )),
+        ),
     ];
 
-    for obj in big_spheres {
-        world.push(obj);
+    for (name, obj) in big_spheres {
+        world.push_named(name, obj);
     }
 
     let camera = Camera::from(
@@ -105,5 +114,5 @@ pub fn large_scene() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
         camera_pos,
     );
 
-    camera.render(world)
+    (camera, world)
 }
@@ -0,0 +1,90 @@
+use crate::camera::Camera;
+use crate::material::{ComplexIor, Conductor, Dielectric, Diffuse, Dispersion, Material, ThinFilm};
+use crate::object::{Object, Sphere, World};
+use crate::spectrum::Illuminant;
+use crate::vec3::{Color, Point3, Vec3};
+
+pub fn materials() -> (Camera, World) {
+    let aspect_ratio = 16. / 9.;
+    let image_width: u32 = 480;
+    let fov = 40.;
+    let focus_distance = 6.;
+    let depth_of_field_angle = 0.;
+    let num_samples = 64;
+    let max_bounce_depth = 16;
+    let camera_pos = Point3::new(0., 1.5, 5.);
+    let target = Point3::new(0., 0.5, 0.);
+    let direction = camera_pos - target;
+    let camera_up = Vec3::new(0., 1., 0.);
+
+    // world
+    let mut world = World::new();
+
+    let mat_grnd = Material::Diffuse(Diffuse::new(&Color::new(0.7, 0.7, 0.7)));
+    // splits white light into a rainbow of caustics
+    let mat_diamond = Material::Dielectric(Dielectric::dispersive(Dispersion::DIAMOND));
+    // green bottle glass, darker where it's thicker
+    let mat_bottle = Material::Dielectric(
+        Dielectric::dispersive(Dispersion::CROWN_GLASS)
+            .with_absorption(&Color::new(0.4, 0.8, 0.5), 1.),
+    );
+    // dense flint glass, described by its cauchy coefficients
+    let mat_flint = Material::Dielectric(Dielectric::dispersive(Dispersion::Cauchy {
+        a: 1.67,
+        b: 0.0145,
+    }));
+    // soap bubble, a film of water around air
+    let mat_bubble =
+        Material::Dielectric(Dielectric::new(1.).with_thin_film(ThinFilm::new(450., 1.33)));
+    // heat tinted metal
+    let mat_tinted = Material::Conductor(
+        Conductor::new(ComplexIor::SILVER, 0.05).with_thin_film(ThinFilm::new(300., 2.4)),
+    );
+
+    world.push_named(
+        "ground",
+        Object::Sphere(Sphere::new(Point3::new(0., -1000., 0.), 1000., mat_grnd)),
+    );
+
+    world.push_named(
+        "diamond",
+        Object::Sphere(Sphere::new(Point3::new(-2.2, 0.5, 0.), 0.5, mat_diamond)),
+    );
+
+    world.push_named(
+        "bottle_glass",
+        Object::Sphere(Sphere::new(Point3::new(-1.1, 0.5, 0.), 0.5, mat_bottle)),
+    );
+
+    world.push_named(
+        "flint_glass",
+        Object::Sphere(Sphere::new(Point3::new(0., 0.5, 0.), 0.5, mat_flint)),
+    );
+
+    world.push_named(
+        "bubble",
+        Object::Sphere(Sphere::new(Point3::new(1.1, 0.5, 0.), 0.5, mat_bubble)),
+    );
+
+    world.push_named(
+        "tinted_metal",
+        Object::Sphere(Sphere::new(Point3::new(2.2, 0.5, 0.), 0.5, mat_tinted)),
+    );
+
+    let camera = Camera::from(
+        aspect_ratio,
+        image_width,
+        num_samples,
+        max_bounce_depth,
+        fov,
+        focus_distance,
+        depth_of_field_angle,
+        direction,
+        camera_up,
+        camera_pos,
+    )
+    // dispersion and interference want every wavelength traced, not just rgb
+    .with_spectral(Illuminant::d65());
+
+    (camera, world)
+}
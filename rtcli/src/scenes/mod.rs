@@ -1,8 +1,36 @@
 mod large_scene;
+mod materials;
+// the first steps of the tracer, standalone images kept for reference
+#[allow(dead_code)]
 mod ray_background;
+#[allow(dead_code)]
 mod ray_sphere;
+mod showcase;
+#[allow(dead_code)]
 mod spectrum;
 mod surface_normals;
 
 pub use large_scene::large_scene;
+pub use materials::materials;
+pub use showcase::showcase;
 pub use surface_normals::surface_normals;
+
+use crate::{camera::Camera, object::World};
+
+/// Builds a scene's camera and world
+pub type Scene = fn() -> (Camera, World);
+
+/// Scenes that can be picked by name from the command line
+pub const SCENES: [(&str, Scene); 4] = [
+    ("large_scene", large_scene),
+    ("materials", materials),
+    ("showcase", showcase),
+    ("surface_normals", surface_normals),
+];
+
+pub fn by_name(name: &str) -> Option<(Camera, World)> {
+    SCENES
+        .iter()
+        .find(|(scene, _)| *scene == name)
+        .map(|(_, scene)| scene())
+}
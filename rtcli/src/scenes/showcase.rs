@@ -0,0 +1,120 @@
+use crate::camera::Camera;
+use crate::material::{
+    AnisotropicMetallic, Coated, ComplexIor, Conductor, Diffuse, Masked, Material, Metallic, Mix,
+    OrenNayar, Sheen, Subsurface,
+};
+use crate::object::{Object, Sphere, World};
+use crate::spectrum::Illuminant;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{Color, Point3, Vec3};
+
+const ROW_LENGTH: usize = 5; // spheres per row, further rows go behind the first
+
+pub fn showcase() -> (Camera, World) {
+    let aspect_ratio = 16. / 9.;
+    let image_width: u32 = 480;
+    let fov = 40.;
+    let focus_distance = 7.;
+    let depth_of_field_angle = 0.;
+    let num_samples = 64;
+    let max_bounce_depth = 16;
+    let camera_pos = Point3::new(0., 2.5, 6.);
+    let target = Point3::new(0., 0.5, -0.6);
+    let direction = camera_pos - target;
+    let camera_up = Vec3::new(0., 1., 0.);
+
+    // world
+    let mut world = World::new();
+
+    let mat_grnd = Material::Diffuse(Diffuse::new(&Color::new(0.7, 0.7, 0.7)));
+    // measured conductors, only their colour differs
+    let mat_gold = Material::Conductor(Conductor::new(ComplexIor::GOLD, 0.1));
+    let mat_copper = Material::Conductor(Conductor::new(ComplexIor::COPPER, 0.1));
+    let mat_aluminium = Material::Conductor(Conductor::new(ComplexIor::ALUMINIUM, 0.1));
+    // a uniform opacity turns the cut out into a see through veil
+    let half_opaque = Texture::SolidColor(SolidColor::new(&Color::new(0.5, 0.5, 0.5)));
+    let mat_veil = Material::Masked(Masked::new(
+        Material::Diffuse(Diffuse::new(&Color::new(0.8, 0.2, 0.2))),
+        half_opaque,
+    ));
+    // amber varnish over pale wood
+    let mat_varnished = Material::Coated(
+        Coated::new(
+            Material::Diffuse(Diffuse::new(&Color::new(0.8, 0.6, 0.4))),
+            1.5,
+        )
+        .with_tint(&Color::new(0.9, 0.7, 0.4)),
+    );
+    // dusty metal, a quarter of the scatters are off the dust
+    let mat_dusty = Material::Mix(Mix::new(
+        Material::Metallic(Metallic::new(&Color::new(0.8, 0.8, 0.8), 0.05)),
+        Material::Diffuse(Diffuse::new(&Color::new(0.5, 0.45, 0.4))),
+        0.25,
+    ));
+    // candle wax, red light wanders furthest before coming back out
+    let mat_wax = Material::Subsurface(Subsurface::new(
+        &Color::new(0.95, 0.9, 0.8),
+        &Color::new(0.2, 0.15, 0.1),
+        1.45,
+    ));
+    // terracotta, flatter than lambertian
+    let mat_clay = Material::OrenNayar(OrenNayar::new(&Color::new(0.7, 0.35, 0.2), 30.));
+    // deep blue velvet with a pale sheen at the silhouette
+    let mat_velvet = Material::Sheen(Sheen::new(
+        &Color::new(0.05, 0.05, 0.3),
+        &Color::new(0.6, 0.6, 0.8),
+        0.4,
+    ));
+    // aluminium brushed around the vertical axis, the highlights stretch pole to pole
+    let mat_brushed = Material::AnisotropicMetallic(AnisotropicMetallic::new(
+        &Color::new(0.9, 0.9, 0.9),
+        0.1,
+        0.5,
+        0.,
+    ));
+
+    let spheres = vec![
+        ("gold", mat_gold),
+        ("copper", mat_copper),
+        ("aluminium", mat_aluminium),
+        ("veil", mat_veil),
+        ("varnished_wood", mat_varnished),
+        ("dusty_metal", mat_dusty),
+        ("wax", mat_wax),
+        ("clay", mat_clay),
+        ("velvet", mat_velvet),
+        ("brushed_metal", mat_brushed),
+    ];
+
+    world.push_named(
+        "ground",
+        Object::Sphere(Sphere::new(Point3::new(0., -1000., 0.), 1000., mat_grnd)),
+    );
+
+    for (i, (name, material)) in spheres.into_iter().enumerate() {
+        let (row, column) = (i / ROW_LENGTH, i % ROW_LENGTH);
+        let x = (column as f32 - (ROW_LENGTH - 1) as f32 / 2.) * 1.1;
+        let z = -(row as f32) * 1.2;
+        world.push_named(
+            name,
+            Object::Sphere(Sphere::new(Point3::new(x, 0.5, z), 0.5, material)),
+        );
+    }
+
+    let camera = Camera::from(
+        aspect_ratio,
+        image_width,
+        num_samples,
+        max_bounce_depth,
+        fov,
+        focus_distance,
+        depth_of_field_angle,
+        direction,
+        camera_up,
+        camera_pos,
+    )
+    // the conductors' colour comes from their spectral refractive index
+    .with_spectral(Illuminant::d65());
+
+    (camera, world)
+}
@@ -2,9 +2,8 @@ use crate::camera::Camera;
 use crate::material::{Dielectric, Diffuse, Material, Metallic};
 use crate::object::{Object, Sphere, World};
 use crate::vec3::{Color, Point3, Vec3};
-use image::ImageBuffer;
 
-pub fn surface_normals() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
+pub fn surface_normals() -> (Camera, World) {
     let aspect_ratio = 16. / 9.;
     let image_width: u32 = 1920;
     let fov = 65.;
@@ -26,35 +25,30 @@ pub fn surface_normals() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
     let mat_bubble = Material::Dielectric(Dielectric::new(1. / 1.5));
     let mat_right = Material::Metallic(Metallic::new(&Color::new(0.8, 0.6, 0.2), 0.0));
 
-    world.push(Object::Sphere(Sphere::new(
-        Point3::new(0., -100.5, -1.),
-        100.,
-        mat_grnd,
-    )));
+    world.push_named(
+        "ground",
+        Object::Sphere(Sphere::new(Point3::new(0., -100.5, -1.), 100., mat_grnd)),
+    );
 
-    world.push(Object::Sphere(Sphere::new(
-        Point3::new(0., 0., -1.2),
-        0.5,
-        mat_center,
-    )));
+    world.push_named(
+        "center",
+        Object::Sphere(Sphere::new(Point3::new(0., 0., -1.2), 0.5, mat_center)),
+    );
 
-    world.push(Object::Sphere(Sphere::new(
-        Point3::new(-1., 0., -1.),
-        0.5,
-        mat_left,
-    )));
+    world.push_named(
+        "left",
+        Object::Sphere(Sphere::new(Point3::new(-1., 0., -1.), 0.5, mat_left)),
+    );
 
-    world.push(Object::Sphere(Sphere::new(
-        Point3::new(-1., 0., -1.),
-        0.4,
-        mat_bubble,
-    )));
+    world.push_named(
+        "bubble",
+        Object::Sphere(Sphere::new(Point3::new(-1., 0., -1.), 0.4, mat_bubble)),
+    );
 
-    world.push(Object::Sphere(Sphere::new(
-        Point3::new(1., 0., -1.),
-        0.5,
-        mat_right,
-    )));
+    world.push_named(
+        "right",
+        Object::Sphere(Sphere::new(Point3::new(1., 0., -1.), 0.5, mat_right)),
+    );
     let camera = Camera::from(
         aspect_ratio,
         image_width,
@@ -68,5 +62,5 @@ pub fn surface_normals() -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
         camera_pos,
     );
 
-    camera.render(world)
+    (camera, world)
 }
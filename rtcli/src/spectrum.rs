@@ -0,0 +1,443 @@
+use std::{
+    io::{self, Read, Write},
+    ops::Mul,
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use crate::{checkpoint::Persist, prelude::*, utils::rng::random_float_range, vec3::Color};
+
+// visible range the integrator samples wavelengths from, in nm
+pub const WAVELENGTH_MIN: f32 = 380.;
+pub const WAVELENGTH_MAX: f32 = 780.;
+
+// wavelength of the sodium d-line, the reference for quoted refractive indices
+pub const WAVELENGTH_D_LINE: f32 = 587.6;
+
+// wavelengths standing in for the red, green and blue channels when a wavelength
+// dependent quantity has to be evaluated in rgb
+pub const RGB_WAVELENGTHS: [f32; 3] = [630., 532., 465.];
+
+pub fn sample_wavelength() -> f32 {
+    random_float_range(WAVELENGTH_MIN..WAVELENGTH_MAX)
+}
+
+/// Linear sRGB weight of a single wavelength, normalised so that averaging it over
+/// uniformly sampled wavelengths in the visible range gives white.
+/// Multiplying a path's throughput by this turns a monochromatic path into an
+/// unbiased estimate of the RGB one.
+pub fn wavelength_to_rgb(wavelength: f32) -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    let white = WHITE.get_or_init(|| {
+        const STEPS: u32 = 400;
+        let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / STEPS as f32;
+        let mut sum = Color::new(0., 0., 0.);
+        for i in 0..STEPS {
+            sum += xyz_to_rgb(cie_xyz(WAVELENGTH_MIN + (i as f32 + 0.5) * step));
+        }
+        sum / STEPS as f32
+    });
+
+    let rgb = xyz_to_rgb(cie_xyz(wavelength));
+    Color::new(rgb.r() / white.r(), rgb.g() / white.g(), rgb.b() / white.b())
+}
+
+/// CIE 1931 2° colour matching functions using the multi-lobe gaussian fit from
+/// Wyman, Sloan & Shirley - "Simple Analytic Approximations to the CIE XYZ
+/// Color Matching Functions" (2013)
+pub fn cie_xyz(wavelength: f32) -> (f32, f32, f32) {
+    // piecewise gaussian with a different spread either side of the mean
+    fn g(x: f32, mean: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+        let sigma = if x < mean { sigma_low } else { sigma_high };
+        let t = (x - mean) / sigma;
+        (-0.5 * t * t).exp()
+    }
+
+    let l = wavelength;
+    let x = 1.056 * g(l, 599.8, 37.9, 31.0) + 0.362 * g(l, 442.0, 16.0, 26.7)
+        - 0.065 * g(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * g(l, 568.8, 46.9, 40.5) + 0.286 * g(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * g(l, 437.0, 11.8, 36.0) + 0.681 * g(l, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+// XYZ to linear sRGB (D65)
+pub fn xyz_to_rgb((x, y, z): (f32, f32, f32)) -> Color {
+    Color::new(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.969266 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+// number of wavelengths carried by each path in spectral mode
+pub const SPECTRUM_SAMPLES: usize = 4;
+
+/// Wavelengths a single path is traced at, a uniformly sampled hero wavelength plus
+/// others rotated evenly through the visible range (Wilkie et al. 2014 - "Hero
+/// Wavelength Spectral Sampling")
+#[derive(Debug, Clone, Copy)]
+pub struct SampledWavelengths {
+    lambda: [f32; SPECTRUM_SAMPLES],
+    pdf: [f32; SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample() -> Self {
+        let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+        let hero = sample_wavelength();
+
+        let mut lambda = [hero; SPECTRUM_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            let offset = i as f32 * range / SPECTRUM_SAMPLES as f32;
+            *l = WAVELENGTH_MIN + (hero - WAVELENGTH_MIN + offset) % range;
+        }
+
+        Self {
+            lambda,
+            pdf: [1. / range; SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    pub fn lambda(&self) -> &[f32; SPECTRUM_SAMPLES] {
+        &self.lambda
+    }
+
+    /// Drops every wavelength but the hero, needed once a path has been bent by a
+    /// dispersive surface as the others would have taken different directions
+    pub fn terminate_secondary(&mut self) {
+        if self.is_secondary_terminated() {
+            return;
+        }
+        for pdf in self.pdf.iter_mut().skip(1) {
+            *pdf = 0.;
+        }
+        self.pdf[0] /= SPECTRUM_SAMPLES as f32;
+    }
+
+    pub fn is_secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|pdf| *pdf == 0.)
+    }
+
+    /// Film response of the radiance carried at these wavelengths in linear sRGB,
+    /// white balanced so that D65, the sRGB white point, comes out neutral
+    pub fn to_rgb(self, radiance: &SampledSpectrum) -> Color {
+        static D65_WHITE: OnceLock<Color> = OnceLock::new();
+        let white = D65_WHITE.get_or_init(|| {
+            const STEPS: u32 = 400;
+            let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / STEPS as f32;
+            let d65 = Illuminant::d65();
+            let mut sum = Color::new(0., 0., 0.);
+            for i in 0..STEPS {
+                let l = WAVELENGTH_MIN + (i as f32 + 0.5) * step;
+                sum += wavelength_to_rgb(l) * d65.power(l);
+            }
+            sum / STEPS as f32
+        });
+
+        let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+        let mut rgb = Color::new(0., 0., 0.);
+        for i in 0..SPECTRUM_SAMPLES {
+            if self.pdf[i] == 0. {
+                continue;
+            }
+            rgb += wavelength_to_rgb(self.lambda[i]) * (radiance.0[i] / (self.pdf[i] * range));
+        }
+        rgb /= SPECTRUM_SAMPLES as f32;
+        Color::new(rgb.r() / white.r(), rgb.g() / white.g(), rgb.b() / white.b())
+    }
+}
+
+/// Spectral quantity evaluated at the wavelengths of a `SampledWavelengths`
+#[derive(Debug, Clone, Copy)]
+pub struct SampledSpectrum(pub [f32; SPECTRUM_SAMPLES]);
+
+impl SampledSpectrum {
+    pub fn splat(value: f32) -> Self {
+        Self([value; SPECTRUM_SAMPLES])
+    }
+
+    pub fn from_fn(wavelengths: &SampledWavelengths, f: impl Fn(f32) -> f32) -> Self {
+        Self(wavelengths.lambda.map(f))
+    }
+
+    /// Upsamples a reflectance to a smooth spectrum. The basis functions sum to one at
+    /// every wavelength, so white stays flat and albedos in 0..=1 stay in 0..=1
+    pub fn from_rgb(color: &Color, wavelengths: &SampledWavelengths) -> Self {
+        Self::from_fn(wavelengths, |l| {
+            let (r, g, b) = rgb_basis(l);
+            color.r() * r + color.g() * g + color.b() * b
+        })
+    }
+}
+
+// partition of unity over the visible range peaking at the red, green and blue primaries
+fn rgb_basis(wavelength: f32) -> (f32, f32, f32) {
+    let g = |mean: f32, sigma: f32| {
+        let t = (wavelength - mean) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    let (r, g, b) = (g(610., 40.), g(545., 30.), g(455., 30.));
+    let sum = r + g + b;
+    (r / sum, g / sum, b / sum)
+}
+
+// SampledSpectrum * SampledSpectrum
+impl Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn mul(self, rhs: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum(std::array::from_fn(|i| self.0[i] * rhs.0[i]))
+    }
+}
+
+// SampledSpectrum * f32
+impl Mul<f32> for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn mul(self, t: f32) -> SampledSpectrum {
+        SampledSpectrum(self.0.map(|v| v * t))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IlluminantSpectrum {
+    Equal,
+    D65,
+    Blackbody(f32), // temperature in kelvin
+}
+
+/// Spectral power distribution lighting the scene in spectral mode, scaled to the
+/// same luminance as an equal energy illuminant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Illuminant {
+    spectrum: IlluminantSpectrum,
+    scale: f32,
+}
+
+impl Illuminant {
+    pub fn equal() -> Self {
+        Self::normalised(IlluminantSpectrum::Equal)
+    }
+
+    // CIE standard daylight
+    pub fn d65() -> Self {
+        Self::normalised(IlluminantSpectrum::D65)
+    }
+
+    // CIE illuminant A, an incandescent tungsten filament
+    pub fn tungsten() -> Self {
+        Self::blackbody(2856.)
+    }
+
+    pub fn blackbody(kelvin: f32) -> Self {
+        Self::normalised(IlluminantSpectrum::Blackbody(kelvin))
+    }
+
+    fn normalised(spectrum: IlluminantSpectrum) -> Self {
+        const STEPS: u32 = 400;
+        let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / STEPS as f32;
+
+        let (mut y, mut y_lit) = (0., 0.);
+        for i in 0..STEPS {
+            let l = WAVELENGTH_MIN + (i as f32 + 0.5) * step;
+            y += cie_xyz(l).1;
+            y_lit += cie_xyz(l).1 * spectrum.power(l);
+        }
+
+        Self {
+            spectrum,
+            scale: y / y_lit,
+        }
+    }
+
+    pub fn power(&self, wavelength: f32) -> f32 {
+        self.scale * self.spectrum.power(wavelength)
+    }
+
+    pub fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum::from_fn(wavelengths, |l| self.power(l))
+    }
+}
+
+// the spectrum, normalised again when it's read back
+impl Persist for Illuminant {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        match self.spectrum {
+            IlluminantSpectrum::Equal => 0u32.write(out),
+            IlluminantSpectrum::D65 => 1u32.write(out),
+            IlluminantSpectrum::Blackbody(kelvin) => {
+                2u32.write(out)?;
+                kelvin.write(out)
+            }
+        }
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        Ok(match u32::read(input)? {
+            0 => Illuminant::equal(),
+            1 => Illuminant::d65(),
+            2 => Illuminant::blackbody(f32::read(input)?),
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown illuminant {tag}"),
+                ))
+            }
+        })
+    }
+}
+
+impl FromStr for Illuminant {
+    type Err = Error;
+
+    /// Parses `equal`, `d65`, `tungsten`, or `blackbody` with an optional `=kelvin`,
+    /// 6500 by default
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, parameter) = match s.split_once('=') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (s, None),
+        };
+
+        match (name.to_ascii_lowercase().as_str(), parameter) {
+            ("equal", None) => Ok(Illuminant::equal()),
+            ("d65", None) => Ok(Illuminant::d65()),
+            ("tungsten", None) => Ok(Illuminant::tungsten()),
+            ("blackbody", kelvin) => {
+                let kelvin = match kelvin {
+                    Some(kelvin) => kelvin
+                        .parse()
+                        .ok()
+                        .filter(|kelvin: &f32| *kelvin > 0.)
+                        .ok_or_else(|| Error::Generic(format!("invalid temperature {kelvin}")))?,
+                    None => 6500.,
+                };
+                Ok(Illuminant::blackbody(kelvin))
+            }
+            _ => Err(Error::Generic(format!(
+                "unknown illuminant {s}, expected equal, d65, tungsten or blackbody[=kelvin]"
+            ))),
+        }
+    }
+}
+
+impl IlluminantSpectrum {
+    fn power(&self, wavelength: f32) -> f32 {
+        match self {
+            IlluminantSpectrum::Equal => 1.,
+            IlluminantSpectrum::D65 => {
+                interpolate(&D65, WAVELENGTH_MIN, D65_STEP, wavelength)
+            }
+            IlluminantSpectrum::Blackbody(kelvin) => {
+                // planck's law with λ in µm, constant factors are normalised out
+                const C2: f32 = 14387.77; // second radiation constant in µm K
+                let l = wavelength / 1000.;
+                1. / (l.powi(5) * ((C2 / (l * kelvin)).exp() - 1.))
+            }
+        }
+    }
+}
+
+// linearly interpolates a table sampled every `step` nm from `start`
+pub fn interpolate(table: &[f32], start: f32, step: f32, wavelength: f32) -> f32 {
+    let x = ((wavelength - start) / step).clamp(0., (table.len() - 1) as f32);
+    let i = (x as usize).min(table.len() - 2);
+    let t = x - i as f32;
+    table[i] * (1. - t) + table[i + 1] * t
+}
+
+// CIE D65 relative spectral power, 380nm to 780nm in 10nm steps
+const D65_STEP: f32 = 10.;
+const D65: [f32; 41] = [
+    49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100., 96.3342, 95.788,
+    88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842,
+    69.7213, 71.6091, 74.349, 61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054, 63.3828,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rng;
+
+    fn assert_near(color: Color, expected: Color, tolerance: f32) {
+        let difference = color - expected;
+        let error = difference.r().abs().max(difference.g().abs()).max(difference.b().abs());
+        assert!(error < tolerance, "{color:?} isn't {expected:?}");
+    }
+
+    // average film response to `illuminant` over many wavelength samples
+    fn response(illuminant: Illuminant, terminated: bool) -> Color {
+        let samples = 50_000;
+        let mut sum = Color::new(0., 0., 0.);
+        for _ in 0..samples {
+            let mut wavelengths = SampledWavelengths::sample();
+            if terminated {
+                wavelengths.terminate_secondary();
+            }
+            sum += wavelengths.to_rgb(&illuminant.sample(&wavelengths));
+        }
+        sum / samples as f32
+    }
+
+    #[test]
+    fn d65_is_white_balanced_to_neutral() {
+        rng::seed(7);
+        let white = Color::new(1., 1., 1.);
+        assert_near(response(Illuminant::d65(), false), white, 0.02);
+        // dropping the secondary wavelengths adds noise but no bias
+        assert_near(response(Illuminant::d65(), true), white, 0.04);
+
+        // warmer light comes out warmer
+        let tungsten = response(Illuminant::tungsten(), false);
+        assert!(tungsten.r() > 1. && tungsten.b() < 1., "tungsten is {tungsten:?}");
+    }
+
+    #[test]
+    fn wavelengths_average_to_white() {
+        let steps = 4000;
+        let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / steps as f32;
+        let sum = (0..steps)
+            .map(|i| wavelength_to_rgb(WAVELENGTH_MIN + (i as f32 + 0.5) * step))
+            .fold(Color::new(0., 0., 0.), |sum, rgb| sum + rgb);
+        assert_near(sum / steps as f32, Color::new(1., 1., 1.), 1e-3);
+    }
+
+    #[test]
+    fn upsampled_albedos_stay_in_range() {
+        rng::seed(7);
+        for _ in 0..1000 {
+            let wavelengths = SampledWavelengths::sample();
+            let white = SampledSpectrum::from_rgb(&Color::new(1., 1., 1.), &wavelengths);
+            assert!(white.0.iter().all(|v| (v - 1.).abs() < 1e-5));
+
+            let albedo = SampledSpectrum::from_rgb(&Color::random(), &wavelengths);
+            assert!(albedo.0.iter().all(|v| (-1e-6..=1. + 1e-6).contains(v)));
+        }
+    }
+
+    #[test]
+    fn illuminants_are_as_bright_as_equal_energy() {
+        let steps = 400;
+        let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / steps as f32;
+        let luminance = |illuminant: Illuminant| {
+            (0..steps)
+                .map(|i| WAVELENGTH_MIN + (i as f32 + 0.5) * step)
+                .map(|l| cie_xyz(l).1 * illuminant.power(l))
+                .sum::<f32>()
+        };
+
+        let equal = luminance(Illuminant::equal());
+        for illuminant in ["d65", "tungsten", "blackbody=4000", "blackbody"] {
+            let lit = luminance(illuminant.parse().unwrap());
+            assert!((lit / equal - 1.).abs() < 1e-3, "{illuminant} is {lit} not {equal}");
+        }
+        assert!("blackbody=0".parse::<Illuminant>().is_err());
+        assert!("d65=6500".parse::<Illuminant>().is_err());
+    }
+}
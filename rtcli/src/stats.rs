@@ -0,0 +1,176 @@
+use std::{
+    cell::Cell,
+    io::{self, Read, Write},
+    ops::Sub,
+    str::FromStr,
+};
+
+use crate::{
+    checkpoint::Persist,
+    prelude::*,
+    progress::{Progress, ProgressObserver},
+};
+
+/// Events counted while rendering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    CameraRays,      // first ray of every path
+    BounceRays,      // rays scattered off a surface
+    PrimitiveTests,  // ray and primitive intersection tests
+    BsdfEvaluations, // materials scattering a ray
+}
+
+impl Counter {
+    pub const ALL: [Counter; 4] = [
+        Counter::CameraRays,
+        Counter::BounceRays,
+        Counter::PrimitiveTests,
+        Counter::BsdfEvaluations,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::CameraRays => "camera_rays",
+            Counter::BounceRays => "bounce_rays",
+            Counter::PrimitiveTests => "primitive_tests",
+            Counter::BsdfEvaluations => "bsdf_evaluations",
+        }
+    }
+}
+
+thread_local! {
+    // counts of this thread's events, never reset so a thread's share of a render is
+    // the difference from before it
+    static COUNTERS: [Cell<u64>; Counter::ALL.len()] = Default::default();
+}
+
+/// Counts an event on the current thread
+pub fn count(counter: Counter) {
+    COUNTERS.with(|counters| {
+        let count = &counters[counter as usize];
+        count.set(count.get() + 1)
+    });
+}
+
+/// Totals of the counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats([u64; Counter::ALL.len()]);
+
+impl Stats {
+    /// Events counted by the current thread so far, subtract an earlier snapshot to
+    /// get the ones in between
+    pub fn thread() -> Self {
+        Self(COUNTERS.with(|counters| counters.each_ref().map(Cell::get)))
+    }
+
+    pub fn get(&self, counter: Counter) -> u64 {
+        self.0[counter as usize]
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        for (count, other) in self.0.iter_mut().zip(other.0) {
+            *count += other;
+        }
+    }
+
+    /// Rays of every kind cast into the scene
+    pub fn rays(&self) -> u64 {
+        self.get(Counter::CameraRays) + self.get(Counter::BounceRays)
+    }
+
+    /// Segments in a path on average, every path starts with a camera ray
+    pub fn average_path_length(&self) -> f64 {
+        match self.get(Counter::CameraRays) {
+            0 => 0.,
+            paths => (paths + self.get(Counter::BounceRays)) as f64 / paths as f64,
+        }
+    }
+
+    /// Counters as a table of names and values, one per line
+    pub fn table(&self) -> String {
+        let mut rows: Vec<_> = Counter::ALL
+            .iter()
+            .map(|&counter| (counter.name(), self.get(counter).to_string()))
+            .collect();
+        rows.push((
+            "average_path_length",
+            format!("{:.3}", self.average_path_length()),
+        ));
+
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        rows.iter()
+            .map(|(name, value)| format!("{name:<width$}  {value:>14}\n"))
+            .collect()
+    }
+
+    /// Counters as a json object keyed by their names
+    pub fn to_json(self) -> String {
+        let mut fields: Vec<_> = Counter::ALL
+            .iter()
+            .map(|&counter| format!("\"{}\":{}", counter.name(), self.get(counter)))
+            .collect();
+        fields.push(format!(
+            "\"average_path_length\":{}",
+            self.average_path_length()
+        ));
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+impl Sub for Stats {
+    type Output = Stats;
+
+    fn sub(self, rhs: Stats) -> Stats {
+        Stats(std::array::from_fn(|i| self.0[i] - rhs.0[i]))
+    }
+}
+
+impl Persist for Stats {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        self.0.iter().try_for_each(|count| count.write(out))
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Self> {
+        let mut stats = Stats::default();
+        for count in &mut stats.0 {
+            *count = u64::read(input)?;
+        }
+        Ok(stats)
+    }
+}
+
+/// How the statistics of a render are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for StatsFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(StatsFormat::Table),
+            "json" => Ok(StatsFormat::Json),
+            _ => Err(Error::Generic(format!(
+                "unknown stats format '{s}', expected table or json"
+            ))),
+        }
+    }
+}
+
+/// Prints the statistics of a render to stdout once it's done
+#[derive(Debug, Clone, Copy)]
+pub struct StatsReport(pub StatsFormat);
+
+impl ProgressObserver for StatsReport {
+    fn on_progress(&self, _progress: &Progress) {}
+
+    fn on_finish(&self, progress: &Progress) {
+        match self.0 {
+            StatsFormat::Table => print!("{}", progress.stats.table()),
+            StatsFormat::Json => println!("{}", progress.stats.to_json()),
+        }
+    }
+}
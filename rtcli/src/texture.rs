@@ -0,0 +1,108 @@
+use std::{path::Path, sync::Arc};
+
+use enum_dispatch::enum_dispatch;
+use image::Rgb32FImage;
+
+use crate::{
+    material::{ParameterHash, Parameters},
+    prelude::*,
+    vec3::{Color, Point3},
+};
+
+#[enum_dispatch(Lookup)]
+#[derive(Debug, Clone)]
+pub enum Texture {
+    SolidColor,   // same value everywhere
+    ImageTexture, // image wrapped over the surface uv coordinates
+}
+
+#[enum_dispatch]
+pub trait Lookup {
+    fn value(&self, u: f32, v: f32, point: &Point3) -> Color;
+}
+
+#[derive(Debug, Clone)]
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: &Color) -> Self {
+        Self { color: *color }
+    }
+}
+
+impl Lookup for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _point: &Point3) -> Color {
+        self.color
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    image: Arc<Rgb32FImage>, // shared so cloning materials doesn't copy pixels
+}
+
+impl ImageTexture {
+    /// Loads an image as linear values, no srgb decoding is done so this suits data
+    /// such as normal and height maps
+    #[allow(dead_code)] // none of the built-in scenes ship with an image to load
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let image = image::open(path)?.into_rgb32f();
+        Ok(Self {
+            image: Arc::new(image),
+        })
+    }
+}
+
+impl Lookup for ImageTexture {
+    fn value(&self, u: f32, v: f32, _point: &Point3) -> Color {
+        let (width, height) = self.image.dimensions();
+
+        // wrap the uvs and flip v so it runs bottom to top, then bilinearly filter
+        // between the four nearest texel centres
+        let x = u.rem_euclid(1.) * width as f32 - 0.5;
+        let y = (1. - v.rem_euclid(1.)) * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let texel = |x: f32, y: f32| {
+            let x = (x as i64).rem_euclid(width as i64) as u32;
+            let y = (y as i64).rem_euclid(height as i64) as u32;
+            let [r, g, b] = self.image.get_pixel(x, y).0;
+            Color::new(r, g, b)
+        };
+
+        let top = (1. - tx) * texel(x0, y0) + tx * texel(x0 + 1., y0);
+        let bottom = (1. - tx) * texel(x0, y0 + 1.) + tx * texel(x0 + 1., y0 + 1.);
+        (1. - ty) * top + ty * bottom
+    }
+}
+
+impl Parameters for Texture {
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        match self {
+            Texture::SolidColor(solid) => hash.color(&solid.color),
+            Texture::ImageTexture(image) => image.hash_parameters(hash),
+        }
+    }
+}
+
+impl Parameters for ImageTexture {
+    // the size and a grid of texels rather than every one of them
+    fn hash_parameters(&self, hash: &mut ParameterHash) {
+        const GRID: u32 = 4;
+        let (width, height) = self.image.dimensions();
+        hash.bytes(&width.to_le_bytes());
+        hash.bytes(&height.to_le_bytes());
+        if width == 0 || height == 0 {
+            return;
+        }
+        for i in 0..GRID {
+            for j in 0..GRID {
+                let [r, g, b] = self.image.get_pixel(width * i / GRID, height * j / GRID).0;
+                hash.color(&Color::new(r, g, b));
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+// minimal complex number for fresnel equations of absorbing media
+#[derive(Default, Copy, Clone, Debug)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    pub fn real(re: f32) -> Self {
+        Self { re, im: 0. }
+    }
+
+    pub fn norm_sq(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn sqrt(&self) -> Complex {
+        let n = self.norm_sq().sqrt();
+        if n == 0. {
+            return Complex::real(0.);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+        if self.re >= 0. {
+            Complex::new(t1, t2)
+        } else {
+            Complex::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+
+    // e^(re + i * im)
+    pub fn exp(&self) -> Complex {
+        let scale = self.re.exp();
+        Complex::new(scale * self.im.cos(), scale * self.im.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Complex {
+        let scale = 1. / rhs.norm_sq();
+        Complex::new(
+            scale * (self.re * rhs.re + self.im * rhs.im),
+            scale * (self.im * rhs.re - self.re * rhs.im),
+        )
+    }
+}
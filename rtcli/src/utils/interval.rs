@@ -5,6 +5,7 @@ pub struct Interval {
     pub max: f32,
 }
 
+#[allow(dead_code)] // the general purpose constructors, hits only need `from`
 impl Interval {
     pub fn new() -> Self {
         Interval::from(f32::INFINITY, f32::NEG_INFINITY)
@@ -4,6 +4,7 @@ pub fn discriminant(a: f32, h: f32, c: f32) -> f32 {
     h * h - a * c
 }
 
+#[allow(dead_code)] // only the ray_sphere step, which nothing renders any more
 pub fn discriminant_intersections(a: f32, b: f32, c: f32) -> Option<u8> {
     let d = discriminant(a, b, c);
     if  d > 0. {
@@ -1,3 +1,4 @@
+pub mod complex;
 pub mod interval;
 pub mod rng;
 pub mod math;
@@ -1,13 +1,125 @@
-use std::ops::Range;
+use std::{
+    cell::{Cell, RefCell},
+    ops::Range,
+};
 
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::sampler::{self, Sample, Sampler};
+
+// dimensions set aside for the camera (pixel position, lens, wavelength) and for each
+// bounce of a path, so the same decision gets the same dimension in every sample
+const CAMERA_DIMENSIONS: u32 = 8;
+const BOUNCE_DIMENSIONS: u32 = 8;
+
+#[derive(Clone, Copy)]
+struct SampleState {
+    sampler: Sampler,
+    pixel: (u32, u32),
+    index: u32,
+    bounce: u32,
+    dimension: u32, // next dimension to hand out
+    end: u32,       // end of the dimensions of the current camera or bounce stage
+    overflow: u32,  // numbers drawn past the end of their stage
+}
+
+thread_local! {
+    static SAMPLE: Cell<Option<SampleState>> = const { Cell::new(None) };
+    // numbers drawn outside of samples, like while building a scene
+    static FALLBACK: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Seeds the numbers this thread draws outside of samples, so scenes built from
+/// random numbers come out the same every run
+pub fn seed(seed: u64) {
+    FALLBACK.with_borrow_mut(|rng| *rng = StdRng::seed_from_u64(seed));
+}
+
+/// Draws the random numbers of this thread from `sampler` as the `index`th sample of
+/// `pixel`, starting with the camera's dimensions, until `end_sample`
+pub fn start_sample(sampler: Sampler, pixel: (u32, u32), index: u32) {
+    SAMPLE.set(Some(SampleState {
+        sampler,
+        pixel,
+        index,
+        bounce: 0,
+        dimension: 0,
+        end: CAMERA_DIMENSIONS,
+        overflow: 0,
+    }));
+}
+
+/// Moves the current sample on to the dimensions of the next bounce of its path
+pub fn next_bounce() {
+    if let Some(mut state) = SAMPLE.get() {
+        state.dimension = CAMERA_DIMENSIONS + state.bounce * BOUNCE_DIMENSIONS;
+        state.end = state.dimension + BOUNCE_DIMENSIONS;
+        state.bounce += 1;
+        SAMPLE.set(Some(state));
+    }
+}
+
+pub fn end_sample() {
+    SAMPLE.set(None);
+}
+
+// where the next `count` numbers come from
+enum Source {
+    Sampler(SampleState, u32), // the sampler, from this dimension on
+    Overflow(SampleState),     // a hash of the sample, past the end of the stage
+    Fallback,                  // the thread's generator, outside of samples
+}
+
+// claims the next `count` dimensions of the current sample
+fn next_dimensions(count: u32) -> Source {
+    let Some(mut state) = SAMPLE.get() else {
+        return Source::Fallback;
+    };
+    let dimension = state.dimension;
+    if dimension + count > state.end {
+        let overflowed = state;
+        state.overflow += count;
+        SAMPLE.set(Some(state));
+        return Source::Overflow(overflowed);
+    }
+    state.dimension += count;
+    SAMPLE.set(Some(state));
+    Source::Sampler(state, dimension)
+}
+
+// uniform number for the `n`th overflowing draw of a sample, hashed rather than drawn
+// from a generator so a sample comes out the same whichever thread takes it
+fn overflow_float(state: &SampleState, n: u32) -> f32 {
+    let (x, y) = state.pixel;
+    sampler::to_unit_float(sampler::hash(&[x, y, state.index, u32::MAX, n]))
+}
 
 pub fn random_float() -> f32 {
-    let mut rng = thread_rng();
-    rng.gen_range(0.0f32..1.0f32)
+    match next_dimensions(1) {
+        Source::Sampler(state, dimension) => {
+            state.sampler.get_1d(state.pixel, state.index, dimension)
+        }
+        Source::Overflow(state) => overflow_float(&state, state.overflow),
+        Source::Fallback => FALLBACK.with_borrow_mut(|rng| rng.gen_range(0.0f32..1.0f32)),
+    }
+}
+
+/// Pair of random numbers, which samplers can stratify together
+pub fn random_2d() -> (f32, f32) {
+    match next_dimensions(2) {
+        Source::Sampler(state, dimension) => {
+            state.sampler.get_2d(state.pixel, state.index, dimension)
+        }
+        Source::Overflow(state) => (
+            overflow_float(&state, state.overflow),
+            overflow_float(&state, state.overflow + 1),
+        ),
+        Source::Fallback => FALLBACK.with_borrow_mut(|rng| {
+            (rng.gen_range(0.0f32..1.0f32), rng.gen_range(0.0f32..1.0f32))
+        }),
+    }
 }
 
 pub fn random_float_range(range: Range<f32>) -> f32 {
-    let mut rng = thread_rng();
-    rng.gen_range(range)
-}
\ No newline at end of file
+    range.start + (range.end - range.start) * random_float()
+}
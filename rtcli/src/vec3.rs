@@ -1,12 +1,10 @@
 use std::{
     fmt::Display,
-    ops::{Add, AddAssign, DerefMut, Div, DivAssign, Mul, MulAssign, Neg, Range, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Range, Sub, SubAssign},
 };
 
-use rand::random;
-
 use crate::utils::{
-    rng::{random_float, random_float_range},
+    rng::{random_2d, random_float, random_float_range},
     Interval,
 };
 
@@ -95,11 +93,20 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_circle_xy() -> Vec3 {
-        Self::random_in_unit_dim(Vec3::new(1., 1., 0.))
+        // polar mapping of a pair of numbers so samplers can stratify the disk
+        let (u, v) = random_2d();
+        let r = u.sqrt();
+        let phi = 2. * core::f32::consts::PI * v;
+        Vec3::new(r * phi.cos(), r * phi.sin(), 0.)
     }
 
     pub fn random_unit() -> Vec3 {
-        Self::random_in_unit_sphere().unit()
+        // uniform on the sphere from a pair of numbers so samplers can stratify it
+        let (u, v) = random_2d();
+        let z = 1. - 2. * u;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * core::f32::consts::PI * v;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
     }
 
     pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
@@ -116,12 +123,24 @@ impl Vec3 {
         self.0.abs() < LIMIT && self.1.abs() < LIMIT && self.2.abs() < LIMIT
     }
 
+    /// Two unit vectors completing an orthonormal basis with unit vector `self`
+    /// Duff et al. - "Building an Orthonormal Basis, Revisited" (2017)
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let sign = 1.0f32.copysign(self.2);
+        let a = -1. / (sign + self.2);
+        let b = self.0 * self.1 * a;
+        (
+            Vec3(1. + sign * self.0 * self.0 * a, sign * b, -sign * self.0),
+            Vec3(b, sign + self.1 * self.1 * a, -self.1),
+        )
+    }
+
     pub fn reflect(&self, normal: &Vec3) -> Vec3 {
         // reflection of vector is V - 2b
         // where b is distance from V to surface
         // because V points into the surface b
         // needs to be negated
-        *self - (2. * normal.clone() * self.dot(normal))
+        *self - (2. * *normal * self.dot(normal))
     }
 
     /// This returns a vector resulting in the refraction of incident vector `self`.
@@ -132,13 +151,13 @@ impl Vec3 {
     /// Params:
     /// * `normal` - surface normal of the material hit
     /// * `ri ratio` - ratio of refractive index:
-    /// [material incident vector is in] / [material hit]
+    ///   [material incident vector is in] / [material hit]
     pub fn refract(&self, normal: &Vec3, ri_ratio: f32) -> Vec3 {
         let cos_theta = 1.0f32.min((-*self).dot(normal));
 
         // type inference fking up here??
-        let r_perpendicular = ri_ratio * (*self + cos_theta * normal.clone());
-        let r_parallel = -((1. - r_perpendicular.len_sq()).abs().sqrt()) * normal.clone();
+        let r_perpendicular = ri_ratio * (*self + cos_theta * *normal);
+        let r_parallel = -((1. - r_perpendicular.len_sq()).abs().sqrt()) * *normal;
 
         r_perpendicular + r_parallel
     }
@@ -189,8 +208,8 @@ impl MulAssign<f32> for Vec3 {
 // Vec3 /= f32
 impl DivAssign<f32> for Vec3 {
     fn div_assign(&mut self, t: f32) {
-        self.2 /= t;
-        self.2 /= t;
+        self.0 /= t;
+        self.1 /= t;
         self.2 /= t;
     }
 }
@@ -272,7 +291,7 @@ impl Color {
         self.2
     }
 
-    pub fn to_rgb(&self) -> [u8; 3] {
+    pub fn to_rgb(self) -> [u8; 3] {
         let intensity = Interval::from(0., 0.999);
         [
             (intensity.clamp(self.r()) * 256.) as u8,
@@ -281,7 +300,12 @@ impl Color {
         ]
     }
 
-    pub fn to_gamma(&self) -> Color {
+    // relative luminance using the rec. 709 primaries
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+    }
+
+    pub fn to_gamma(self) -> Color {
         Color(
             Self::linear_to_gamma(self.r()),
             Self::linear_to_gamma(self.g()),
@@ -289,6 +313,18 @@ impl Color {
         )
     }
 
+    // encodes linear values with the srgb transfer function
+    pub fn to_srgb(self) -> Color {
+        let encode = |linear: f32| {
+            if linear <= 0.0031308 {
+                12.92 * linear.max(0.)
+            } else {
+                1.055 * linear.powf(1. / 2.4) - 0.055
+            }
+        };
+        Color(encode(self.0), encode(self.1), encode(self.2))
+    }
+
     fn linear_to_gamma(linear: f32) -> f32 {
         if linear > 0. {
             linear.sqrt()
@@ -305,6 +341,11 @@ impl Color {
     pub fn random() -> Self {
         Color::new(random_float(), random_float(), random_float())
     }
+
+    // component-wise e^x
+    pub fn exp(&self) -> Color {
+        Color(self.0.exp(), self.1.exp(), self.2.exp())
+    }
 }
 
 impl Display for Color {
@@ -353,8 +394,8 @@ impl MulAssign<f32> for Color {
 // Color /= f32
 impl DivAssign<f32> for Color {
     fn div_assign(&mut self, t: f32) {
-        self.2 /= t;
-        self.2 /= t;
+        self.0 /= t;
+        self.1 /= t;
         self.2 /= t;
     }
 }